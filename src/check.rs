@@ -0,0 +1,237 @@
+// a static check of a script, it reports problems found otherwise at running targets
+use std::rc::Rc;
+
+use crate::{
+    fun::{BlockType, GenBlock, GenBlockTup},
+    lex::{self, ScriptError},
+    log::Log,
+};
+
+/// built-in functions with a minimal and a maximal number of parameters, None for any number
+pub const FUNCTIONS: &[(&str, usize, Option<usize>)] = &[
+    ("absolute", 1, Some(1)),
+    ("aexec", 0, None),
+    ("and", 0, None),
+    ("anynewer", 2, Some(2)),
+    ("array", 0, None),
+    ("as_jar", 1, Some(1)),
+    ("as_url", 1, Some(1)),
+    ("ask", 1, Some(2)),
+    ("assign", 1, Some(2)),
+    ("break", 0, Some(0)),
+    ("calc", 1, None),
+    ("canonicalize", 1, Some(1)),
+    ("cfg", 0, Some(0)),
+    ("changed", 2, Some(2)),
+    ("contains", 2, Some(2)),
+    ("continue", 0, Some(0)),
+    ("cp", 2, None),
+    ("cropname", 2, Some(3)),
+    ("depinfo", 2, Some(2)),
+    ("display", 0, Some(1)),
+    ("element", 2, Some(3)),
+    ("entry", 2, Some(3)),
+    ("env", 1, Some(1)),
+    ("eq", 1, Some(2)),
+    ("exec", 0, None),
+    ("exec?", 0, None),
+    ("file_filter", 1, None),
+    ("filename", 1, Some(1)),
+    ("files", 1, None),
+    ("filter", 1, None),
+    ("find", 2, Some(2)),
+    ("gt", 2, Some(2)),
+    ("include", 1, Some(1)),
+    ("join", 1, Some(2)),
+    ("keys", 1, Some(1)),
+    ("lt", 2, Some(2)),
+    ("mkd", 1, None),
+    ("mv", 2, None),
+    ("neq", 1, Some(2)),
+    ("newerthan", 1, Some(2)),
+    ("not", 1, Some(1)),
+    ("now", 0, Some(1)),
+    ("number", 1, Some(1)),
+    ("or", 0, None),
+    ("panic", 0, Some(1)),
+    ("prompt", 1, Some(2)),
+    ("range", 2, Some(3)),
+    ("read", 1, Some(1)),
+    ("remove", 2, Some(2)),
+    ("return", 0, Some(1)),
+    ("rm", 1, None),
+    ("rmdir", 1, None),
+    ("rmdira", 1, None),
+    ("scalar", 1, Some(2)),
+    ("set_env", 2, Some(2)),
+    ("slice", 2, Some(3)),
+    ("split", 1, Some(2)),
+    ("stop", 0, Some(0)),
+    ("timestamp", 1, Some(2)),
+    ("values", 1, Some(1)),
+    ("write", 1, None),
+    ("writea", 1, None),
+    ("writex", 1, None),
+    ("zip", 1, None),
+];
+
+/// functions which can be a dependency
+const DEP_FUNCTIONS: &[(&str, usize, Option<usize>)] = &[
+    ("anynewer", 2, Some(2)),
+    ("changed", 2, Some(2)),
+    ("depinfo", 2, Some(2)),
+    ("target", 1, Some(1)),
+];
+
+/// reports all found problems and returns a number of them, the lexer errors are counted too
+pub fn check(log: &Log, block: &GenBlockTup) -> usize {
+    let issues = issues(block);
+    for issue in &issues {
+        log.error(&issue.to_string())
+    }
+    lex::recovered_errors() + issues.len()
+}
+
+/// problems found in the script tree, the lexer errors aren't included
+pub fn issues(block: &GenBlockTup) -> Vec<ScriptError> {
+    let mut issues = Vec::new();
+    check_block(&mut issues, block);
+    issues
+}
+
+fn check_block(issues: &mut Vec<ScriptError>, block: &GenBlockTup) {
+    let naked_block = block.borrow();
+    match naked_block.block_type {
+        BlockType::Function => {
+            let name = naked_block.name.as_deref().unwrap_or_default();
+            match block.search_fun(name) {
+                Some(fun_def) if !FUNCTIONS.iter().any(|(fun_name, ..)| *fun_name == name) => {
+                    let expected = fun_def.borrow().params.len();
+                    let len = naked_block.params.len();
+                    if len != expected {
+                        issue(
+                            issues,
+                            &naked_block,
+                            &format!("Function '{name}' requires {expected} parameters, but specified {len}"),
+                        );
+                    }
+                }
+                _ => check_function(issues, &naked_block, FUNCTIONS),
+            }
+            if matches!(name, "break" | "continue") && !naked_block.in_loop() {
+                issue(issues, &naked_block, &format!("{name}() can be used only in a loop"));
+            }
+        }
+        BlockType::Fun => {
+            let name = naked_block.name.as_deref().unwrap_or_default();
+            if FUNCTIONS.iter().any(|(fun_name, ..)| *fun_name == name) {
+                issue(issues, &naked_block, &format!("Function {name} is built-in and can't be redefined"));
+            } else if let Some(parent) = &naked_block.parent
+                && let Some(first) = parent.borrow().children.iter().find(|child| {
+                    let child = child.borrow();
+                    child.block_type == BlockType::Fun && child.name.as_deref() == Some(name)
+                })
+                && !Rc::ptr_eq(&first.0, &block.0)
+            {
+                issue(issues, &naked_block, &format!("Function {name} is already defined"));
+            }
+        }
+        BlockType::If
+            if naked_block
+                .children
+                .get(1)
+                .is_none_or(|then| then.borrow().block_type != BlockType::Then) =>
+        {
+            issue(issues, &naked_block, "An 'if' block without 'then'");
+        }
+        BlockType::For if !naked_block.params.is_empty() => {
+            let range = naked_block.dir.as_deref().unwrap_or_default();
+            let len = naked_block.params.len();
+            let msg = match range {
+                "range" if !(2..=3).contains(&len) => {
+                    Some(format!("A 'for' range requires 2 or 3 parameters, but specified {len}"))
+                }
+                "lines" if len != 1 => Some(format!("A 'for' lines require 1 parameter, but specified {len}")),
+                "range" | "lines" => None,
+                range if !FUNCTIONS.iter().any(|(fun_name, ..)| *fun_name == range) => {
+                    Some(format!("No such function {range}"))
+                }
+                _ => None,
+            };
+            if let Some(msg) = msg {
+                issue(issues, &naked_block, &msg);
+            }
+        }
+        BlockType::Elif
+            if naked_block
+                .children
+                .get(1)
+                .is_none_or(|then| then.borrow().block_type != BlockType::Then) =>
+        {
+            issue(issues, &naked_block, "An 'elif' block without 'then'");
+        }
+        _ => (),
+    }
+    if naked_block.block_type == BlockType::If
+        && let Some(else_pos) =
+            naked_block.children.iter().position(|child| child.borrow().block_type == BlockType::Else)
+        && let Some(elif) = naked_block.children[else_pos..]
+            .iter()
+            .find(|child| child.borrow().block_type == BlockType::Elif)
+    {
+        issue(issues, &elif.borrow(), "An 'elif' block after 'else'");
+    }
+    for dep in &naked_block.deps {
+        check_dep(issues, dep)
+    }
+    for child in &naked_block.children {
+        check_block(issues, child)
+    }
+}
+
+fn check_dep(issues: &mut Vec<ScriptError>, dep: &GenBlockTup) {
+    let naked_dep = dep.borrow();
+    let [child] = naked_dep.children.as_slice() else {
+        return naked_dep.children.iter().for_each(|child| check_block(issues, child));
+    };
+    let child_block = child.borrow();
+    if child_block.block_type != BlockType::Function {
+        return check_block(issues, child);
+    }
+    check_function(issues, &child_block, DEP_FUNCTIONS);
+    if child_block.name.as_deref() == Some("target")
+        && let Some(name) = child_block.params.first()
+        && dep.get_target(name).is_none()
+    {
+        issue(issues, &child_block, &format!("Target '{name}' isn't defined"));
+    }
+}
+
+fn check_function(issues: &mut Vec<ScriptError>, fun_block: &GenBlock, functions: &[(&str, usize, Option<usize>)]) {
+    let name = fun_block.name.as_deref().unwrap_or_default();
+    let Some((_, min, max)) = functions.iter().find(|(fun_name, ..)| *fun_name == name) else {
+        if functions == DEP_FUNCTIONS && FUNCTIONS.iter().any(|(fun_name, ..)| *fun_name == name) {
+            issue(issues, fun_block, &format!("Function {name} can't be used as a dependency"))
+        } else {
+            issue(issues, fun_block, &format!("No such function {name}"))
+        }
+        return;
+    };
+    let len = fun_block.params.len();
+    if len < *min || max.is_some_and(|max| len > max) {
+        let expected = match max {
+            Some(max) if max == min => format!("{min}"),
+            Some(max) => format!("{min} to {max}"),
+            None => format!("at least {min}"),
+        };
+        issue(
+            issues,
+            fun_block,
+            &format!("Function '{name}' requires {expected} parameters, but specified {len}"),
+        );
+    }
+}
+
+fn issue(issues: &mut Vec<ScriptError>, block: &GenBlock, msg: &str) {
+    issues.push(block.script_error(msg))
+}
@@ -0,0 +1,391 @@
+// a formatter printing a script in the canonical layout
+use std::{collections::HashMap, error::Error, fs, path::PathBuf, process};
+
+use crate::{
+    lex::{self, Lexem, LineLexem},
+    log::Log,
+};
+
+const INDENT: &str = "    ";
+
+// a function call of several parameters longer than that gets one parameter per line
+const MAX_LINE_LEN: usize = 100;
+
+/// formats the script file in place, returns false when the file is already formatted
+pub fn format(log: &Log, file: &PathBuf) -> Result<bool, Box<dyn Error>> {
+    let source = fs::read_to_string(file)?;
+    let errors = lex::recovered_errors();
+    let lexems = lex::read_lexems(log, file, &source)?;
+    if lex::recovered_errors() > errors {
+        return Err(format!("The script {} has errors and isn't formatted", file.display()).into());
+    }
+    let formatted = Printer::new(&source).print(&lexems)?;
+    if formatted == source {
+        return Ok(false);
+    }
+    // the formatted script has to be read exactly as the original one
+    let same = match lex::read_lexems(log, file, &formatted) {
+        Ok(formatted_lexems) => same_lexems(log, &lexems, &formatted_lexems),
+        Err(_) => false,
+    };
+    if !same || lex::recovered_errors() > errors {
+        return Err(format!("The script {} can't be formatted without changing its meaning", file.display()).into());
+    }
+    let temp = PathBuf::from(format!("{}.{}", file.display(), process::id()));
+    fs::write(&temp, &formatted)?;
+    fs::rename(&temp, file)?;
+    Ok(true)
+}
+
+struct Printer<'a> {
+    lines: Vec<&'a str>, // source lines for locating comments and blank lines
+    source: &'a str,
+    out: String,
+    depth: usize,
+    prev_line: u32,
+}
+
+enum Param {
+    Value(String),
+    Comment(String, bool), // a comment text and it trails a parameter
+}
+
+impl<'a> Printer<'a> {
+    fn new(source: &'a str) -> Self {
+        Printer { lines: source.lines().collect(), source, out: String::new(), depth: 0, prev_line: 0 }
+    }
+
+    fn print(mut self, lexems: &[LineLexem]) -> Result<String, String> {
+        let mut pos = 0;
+        while pos < lexems.len() {
+            let LineLexem { lexem, line, .. } = &lexems[pos];
+            pos += 1;
+            match lexem {
+                Lexem::Comment(comment) => self.comment(comment, *line),
+                Lexem::Variable(name) => {
+                    self.new_line(*line);
+                    self.out += &quote_name(name);
+                    self.out.push_str(" =");
+                    if let Some(LineLexem { lexem: Lexem::Value(value), .. }) = lexems.get(pos) {
+                        pos += 1;
+                        if !value.is_empty() {
+                            let value = self.value(value)?;
+                            self.out.push(' ');
+                            self.out += &value
+                        }
+                        if let Some(LineLexem { lexem: Lexem::Type(var_type), .. }) = lexems.get(pos) {
+                            pos += 1;
+                            self.out.push(':');
+                            self.out += var_type
+                        }
+                    }
+                }
+                Lexem::Value(value) => {
+                    self.new_line(*line);
+                    let value = self.value(value)?;
+                    self.out += &value
+                }
+                Lexem::Type(var_type) => {
+                    self.out.push(':');
+                    self.out += var_type
+                }
+                Lexem::Function(header) => {
+                    let (params, next) = function_params(lexems, pos);
+                    pos = next;
+                    let call = self.call(header, &params, self.depth);
+                    self.new_line(*line);
+                    self.out += &call
+                }
+                Lexem::BlockHdr(header) => {
+                    let header = self.header(header);
+                    let keyword = header.split_whitespace().next().unwrap_or_default();
+                    let open = if header.is_empty() { "{".to_string() } else { format!("{header} {{") };
+                    // 'then' follows its condition, a body follows its function definition or 'for',
+                    // and 'else', 'elif', 'catch', 'finally' follow the closed block on the same line
+                    let joined = match keyword {
+                        "then" => pos >= 2 && lexems[pos - 2].end_function,
+                        "" => pos >= 2 && lexems[pos - 2].end_function && has_body(lexems, pos - 2),
+                        "else" | "elif" | "catch" | "finally" => pos >= 2 && matches!(lexems[pos - 2].lexem, Lexem::BlockEnd(_)),
+                        _ => false,
+                    } && !self.out.ends_with('\n');
+                    let inline = self.inline_block(lexems, pos, &open);
+                    if joined {
+                        self.out.push(' ')
+                    } else {
+                        self.new_line(*line)
+                    }
+                    match inline {
+                        Some((block, next)) => {
+                            self.out += &block;
+                            pos = next
+                        }
+                        None => {
+                            self.out += &open;
+                            self.depth += 1
+                        }
+                    }
+                }
+                Lexem::BlockEnd(value) => {
+                    self.depth = self.depth.saturating_sub(1);
+                    self.start_line();
+                    self.out += &INDENT.repeat(self.depth);
+                    match value {
+                        // a value has to be followed by the brace, because blanks after it are a part of the value
+                        Some(value) if !value.is_empty() => {
+                            self.out += INDENT;
+                            self.out += value
+                        }
+                        _ => (),
+                    }
+                    self.out.push('}')
+                }
+                Lexem::Parameter(_) | Lexem::Range(..) | Lexem::EOF => (),
+            }
+            self.prev_line = lexems[pos - 1].line;
+        }
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n')
+        }
+        Ok(self.out)
+    }
+
+    /// starts a new line keeping one blank line of the source before it
+    fn new_line(&mut self, line: u32) {
+        let blank = !self.out.is_empty()
+            && !self.out.ends_with('{')
+            && (self.prev_line..line).any(|line| {
+                line > 0 && self.lines.get(line as usize - 1).is_some_and(|line| line.trim().is_empty())
+            });
+        self.start_line();
+        if blank {
+            self.out.push('\n')
+        }
+        self.out += &INDENT.repeat(self.depth)
+    }
+
+    fn start_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n')
+        }
+    }
+
+    fn comment(&mut self, comment: &str, line: u32) {
+        let comment = comment.trim_end();
+        let line = self.comment_line(comment, line);
+        if self.trailing(comment, line) && !self.out.is_empty() {
+            self.out.push(' ');
+            self.out += comment
+        } else {
+            self.new_line(line);
+            self.out += comment
+        }
+    }
+
+    /// a comment lexem is read with the end of its line, so the line can be the next one
+    fn comment_line(&self, comment: &str, line: u32) -> u32 {
+        if line > 1 && self.lines.get(line as usize - 2).is_some_and(|src| src.trim_end().ends_with(comment)) {
+            line - 1
+        } else {
+            line
+        }
+    }
+
+    fn trailing(&self, comment: &str, line: u32) -> bool {
+        line > 0 && self.lines.get(line as usize - 1).is_some_and(|src| src.trim() != comment.trim())
+    }
+
+    fn value(&self, value: &str) -> Result<String, String> {
+        if (value.starts_with('[') && value.ends_with(']')) || (value.starts_with('{') && value.ends_with('}')) {
+            // comments inside of an array or a map aren't kept by the lexer
+            if !self.source.contains(value) {
+                return Err(format!("Comments inside of the value {value} can't be kept"));
+            }
+            return Ok(value.to_string());
+        }
+        Ok(quote_value(value))
+    }
+
+    fn header(&self, header: &str) -> String {
+        let header = header.trim();
+        let Some((keyword, rest)) = header.split_once(char::is_whitespace) else {
+            return header.to_string();
+        };
+        if keyword.contains(':') || rest.contains('"') {
+            return header.to_string();
+        }
+        let mut parts: Vec<&str> = rest.split(':').map(str::trim).collect();
+        if parts.len() > 3 {
+            return header.to_string();
+        }
+        while parts.len() > 1 && parts.last().is_some_and(|part| part.is_empty()) {
+            parts.pop();
+        }
+        let rest = parts.join(":");
+        if rest.is_empty() { keyword.to_string() } else { format!("{keyword} {rest}") }
+    }
+
+    fn call(&self, header: &str, params: &[Param], depth: usize) -> String {
+        let name = self.header(header);
+        let values: Vec<String> = params
+            .iter()
+            .filter_map(|param| match param {
+                Param::Value(value) => Some(quote_param(value)),
+                Param::Comment(..) => None,
+            })
+            .collect();
+        let single = format!("{name}({})", values.join(", "));
+        if values.len() == params.len()
+            && !single.contains('\n')
+            && (values.len() < 2 || depth * INDENT.len() + single.len() <= MAX_LINE_LEN)
+        {
+            return single;
+        }
+        let indent = INDENT.repeat(depth + 1);
+        let mut res = format!("{name}(");
+        let mut left = values.len();
+        for param in params {
+            match param {
+                Param::Value(value) => {
+                    left -= 1;
+                    res.push('\n');
+                    if !value.is_empty() {
+                        res += &indent;
+                        res += &quote_param(value)
+                    }
+                    if left > 0 {
+                        res.push(',')
+                    }
+                }
+                Param::Comment(comment, true) => {
+                    res.push(' ');
+                    res += comment
+                }
+                Param::Comment(comment, _) => {
+                    res.push('\n');
+                    res += &indent;
+                    res += comment
+                }
+            }
+        }
+        res.push('\n');
+        res += &INDENT.repeat(depth);
+        res.push(')');
+        res
+    }
+
+    /// a block containing only a value, or a dependency of a single function, is kept in one line
+    fn inline_block(&self, lexems: &[LineLexem], pos: usize, open: &str) -> Option<(String, usize)> {
+        match &lexems.get(pos)?.lexem {
+            Lexem::BlockEnd(value) => Some((format!("{open}{}}}", value.as_deref().unwrap_or_default()), pos + 1)),
+            Lexem::Function(header) if open.starts_with("dependency") => {
+                let (params, next) = function_params(lexems, pos + 1);
+                let Lexem::BlockEnd(value) = &lexems.get(next)?.lexem else {
+                    return None;
+                };
+                if !lexems[next - 1].end_function
+                    || !value.as_deref().unwrap_or_default().is_empty()
+                    || params.iter().any(|param| matches!(param, Param::Comment(..)))
+                {
+                    return None;
+                }
+                let call = self.call(header, &params, self.depth);
+                (!call.contains('\n') && self.depth * INDENT.len() + open.len() + call.len() < MAX_LINE_LEN)
+                    .then(|| (format!("{open}{call}}}"), next + 1))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// checks if a call ending at the position is a definition of a user function, or a 'for' over a function
+fn has_body(lexems: &[LineLexem], end: usize) -> bool {
+    lexems[..end]
+        .iter()
+        .rev()
+        .find_map(|lexem| match &lexem.lexem {
+            Lexem::Function(header) => Some(matches!(header.split_whitespace().next(), Some("fun" | "for"))),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// collects parameters and comments of a function call starting at the position
+fn function_params(lexems: &[LineLexem], mut pos: usize) -> (Vec<Param>, usize) {
+    let mut params = Vec::new();
+    while let Some(LineLexem { lexem, line, end_function }) = lexems.get(pos) {
+        pos += 1;
+        match lexem {
+            Lexem::Parameter(value) => {
+                // an empty parameter of a call with no parameters isn't a parameter
+                if !(value.is_empty() && *end_function && params.is_empty()) {
+                    params.push(Param::Value(value.clone()))
+                }
+                if *end_function {
+                    break;
+                }
+            }
+            Lexem::Comment(comment) => {
+                let trailing = pos >= 2 && lexems[pos - 2].line + 1 >= *line && !matches!(lexems[pos - 2].lexem, Lexem::Comment(_));
+                params.push(Param::Comment(comment.trim_end().to_string(), trailing))
+            }
+            _ => {
+                pos -= 1;
+                break;
+            }
+        }
+    }
+    (params, pos)
+}
+
+fn quote_name(name: &str) -> String {
+    if name.is_empty()
+        || name.trim() != name
+        || name.contains(['=', '(', ')', '{', '}', '[', ']', ';', '#', '"', '\\', '\n', ':'])
+    {
+        format!("\"{}\"", name.replace('"', "\\\""))
+    } else {
+        name.to_string()
+    }
+}
+
+fn quote_value(value: &str) -> String {
+    if value.trim() != value
+        || value.starts_with(['"', '['])
+        || value.ends_with('\\')
+        || value.contains(['#', ';', ':', '\n'])
+    {
+        format!("\"{}\"", value.replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn quote_param(param: &str) -> String {
+    if param.trim() != param
+        || param.starts_with('"')
+        || param.ends_with('\\')
+        || param.contains([',', ')', '#', '\n'])
+    {
+        format!("\"{}\"", param.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        param.to_string()
+    }
+}
+
+/// compares lexems as they are processed, so block headers are compared by their parts
+fn same_lexems(log: &Log, lexems: &[LineLexem], formatted: &[LineLexem]) -> bool {
+    let header = |header: &str| lex::process_lex_header(log, header, &HashMap::new()).ok();
+    lexems.len() == formatted.len()
+        && lexems.iter().zip(formatted).all(|(lexem, formatted)| {
+            lexem.end_function == formatted.end_function
+                && match (&lexem.lexem, &formatted.lexem) {
+                    (Lexem::BlockHdr(hdr1), Lexem::BlockHdr(hdr2))
+                    | (Lexem::Function(hdr1), Lexem::Function(hdr2)) => header(hdr1) == header(hdr2),
+                    (Lexem::BlockEnd(val1), Lexem::BlockEnd(val2)) => {
+                        val1.as_deref().unwrap_or_default() == val2.as_deref().unwrap_or_default()
+                    }
+                    (Lexem::Comment(comm1), Lexem::Comment(comm2)) => comm1.trim_end() == comm2.trim_end(),
+                    (lexem, formatted) => lexem == formatted,
+                }
+        })
+}
@@ -1,10 +1,11 @@
-use crate::lex::{VarType, VarVal, process_template_value};
+use crate::lex::{ScriptError, Value, VarType, VarVal, process_template_value};
 use crate::log::Log;
 use simcolor::Colorized;
 #[cfg(unix)]
 use std::os::unix::fs::OpenOptionsExt;
 use std::{
     cell::RefCell,
+    cmp,
     collections::HashMap,
     env,
     error::Error,
@@ -13,17 +14,22 @@ use std::{
         self, File, OpenOptions, copy, create_dir_all, remove_dir, remove_dir_all, remove_file,
         rename,
     },
-    io::{self, Write},
+    io::{self, BufRead, BufReader, Read, Write},
     ops::Deref,
     path::Path,
     path::{MAIN_SEPARATOR, MAIN_SEPARATOR_STR, PathBuf},
-    process::{Command, Stdio},
+    process::{Command, ExitStatus, Stdio},
     rc::{Rc, Weak},
+    sync::{
+        RwLock,
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 //use http::{Request,Response};
 use crate::CWD;
-use crate::get_property;
+use crate::state;
 use crate::util::{self, format_time, has_root};
 use crate::version;
 
@@ -55,6 +61,11 @@ pub enum BlockType {
     While,
     Case,
     Choice,
+    Fun, // a user defined function
+    Elif,
+    Try,
+    Catch,
+    Finally,
 }
 
 #[derive(/*Debug,*/ Default)]
@@ -105,6 +116,80 @@ enum CalcErrCause {
 
 type CalcErr = (CalcErrCause, usize);
 
+/// a target failure, the target is the one which actually failed, so it can be
+/// a dependency of the executed target
+#[derive(Debug, Clone)]
+pub struct TargetFailure {
+    pub target: String,
+    pub reason: String,
+}
+
+/// a dependency on a target given as target(...) with its location in a script
+#[derive(Debug, Clone)]
+struct TargetDep {
+    name: String,
+    file: String,
+    line: u32,
+}
+
+/// the build failed, a process exit code has to reflect it
+#[derive(Debug)]
+pub struct BuildFailure(pub String);
+
+impl fmt::Display for BuildFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for BuildFailure {}
+
+pub const BUILD_FAILURE_EXIT_CODE: i32 = 2;
+
+/// exit code of a parallel build job which found its target up to date
+pub const JOB_UP_TO_DATE_EXIT_CODE: i32 = 3;
+
+// a failure reported by a function of the currently executed target
+static FAILURE: RwLock<Option<String>> = RwLock::new(None);
+
+// a file collecting inputs of evaluated dependencies for the watch mode, and the inputs
+static WATCH_LIST: RwLock<Option<(String, Vec<String>)>> = RwLock::new(None);
+
+// a name and a location of the function which failed, it's passed to 'catch'
+static FAILURE_ORIGIN: RwLock<Option<(String, String)>> = RwLock::new(None);
+
+// a number of executed 'try' blocks, errors of functions fail them then
+static TRY_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+// a pending break(), continue(), return(), or stop(), executed blocks are left until it's taken
+static JUMP: RwLock<Option<Jump>> = RwLock::new(None);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Jump {
+    Break,
+    Continue,
+    Return, // leaves a user function or a target
+    Stop,   // leaves a target
+}
+
+// an error in the script found at the execution, it stops the build
+static SCRIPT_ERROR: RwLock<Option<ScriptError>> = RwLock::new(None);
+
+// 'changed' evaluated by targets being executed, a state of a target is stored when it succeeds
+static CHANGED_STATES: RwLock<Vec<ChangedState>> = RwLock::new(Vec::new());
+
+/// an evaluated 'changed', its files are hashed again when the target is complete
+struct ChangedState {
+    dir: String,
+    key: String,
+    inputs: String,
+    outputs: String,
+    location: String,
+}
+
+// results of targets known in the current invocation in the order of completing, true - the target was executed
+static TARGET_RESULTS: RwLock<Vec<(String, Result<bool, TargetFailure>)>> = RwLock::new(Vec::new());
+
 #[derive(Clone, Debug)]
 pub struct GenBlockTup(pub Rc<RefCell<GenBlock>>);
 
@@ -171,9 +256,33 @@ impl GenBlock {
         }
     }
 
+    pub fn script_error(&self, msg: impl Into<String>) -> ScriptError {
+        ScriptError {
+            file: self.script_path(),
+            line: self.script_line,
+            column: None,
+            msg: msg.into(),
+        }
+    }
+
+    /// checks if the block is in a loop of the same function or target
+    pub fn in_loop(&self) -> bool {
+        let mut parent = self.parent.clone();
+        while let Some(block) = parent {
+            let block = block.borrow();
+            match block.block_type {
+                BlockType::For | BlockType::While => return true,
+                BlockType::Fun | BlockType::Target | BlockType::Main => return false,
+                _ => parent = block.parent.clone(),
+            }
+        }
+        false
+    }
+
     pub fn script_path(&self) -> String {
-        self.search_up(crate::SCRIPT)
-            .map(|v| v.value)
+        self.search_up(crate::TARGET_SCRIPT)
+            .or_else(|| self.search_up(crate::SCRIPT))
+            .map(|v| v.into_text())
             .unwrap_or_default()
     }
 }
@@ -234,6 +343,26 @@ impl GenBlockTup {
         }
     }
 
+    /// the block enclosing the innermost 'try' the block belongs to, so its 'catch' sees variables set there,
+    /// or the block itself outside of 'try'
+    pub fn outside_try(&self) -> GenBlockTup {
+        let mut current_bl = self.clone();
+        loop {
+            let current_bare = current_bl.borrow();
+            let parent = current_bare.parent.clone();
+            match current_bare.block_type {
+                BlockType::Try => return parent.unwrap_or(self.clone()),
+                BlockType::Target | BlockType::Fun | BlockType::Main => return self.clone(),
+                _ => (),
+            }
+            drop(current_bare);
+            match parent {
+                Some(parent) => current_bl = parent,
+                None => return self.clone(),
+            }
+        }
+    }
+
     pub fn prev_or_search_up(&self, name: &String, prev: &Option<VarVal>) -> Option<VarVal> {
         if PREV_VAL == name {
             prev.clone()
@@ -247,12 +376,19 @@ impl GenBlockTup {
         self.0.borrow().parent.clone()
     }
 
-    pub fn eval_dep(&self, log: &Log, prev_res: &Option<VarVal>) -> bool {
+    pub fn eval_dep(&self, log: &Log, prev_res: &Option<VarVal>) -> Result<bool, TargetFailure> {
         let dep = self.0.borrow();
         let len = dep.children.len();
         //println!{"depb {dep:?}"}
         if len == 0 {
-            return dep.out.is_none() || "true" == dep.out.as_ref().unwrap();
+            let res = dep.out.is_none() || "true" == dep.out.as_ref().unwrap();
+            log.explain(&format!(
+                "dependency {:?} at {}:{} is {res}",
+                dep.out.as_deref().unwrap_or("true"),
+                dep.script_path(),
+                dep.script_line
+            ));
+            return Ok(res);
         } else if len == 1 {
             let dep_task = &dep.children[0];
             let dep_block = dep_task.0.borrow();
@@ -265,7 +401,19 @@ impl GenBlockTup {
                             match target {
                                 Some(target) => {
                                     //let target_bor = target.0.borrow_mut();
-                                    return exec_target(log, &target, false);
+                                    let res = exec_target(log, &target, false);
+                                    log.explain(&format!(
+                                        "dependency target({}) at {}:{} is {}",
+                                        dep_block.params[0],
+                                        dep.script_path(),
+                                        dep.script_line,
+                                        match &res {
+                                            Ok(true) => "true, the target was executed".to_string(),
+                                            Ok(false) => "false, the target was up to date".to_string(),
+                                            Err(failure) => format!("failed, because the target '{}' failed", failure.target),
+                                        }
+                                    ));
+                                    return res;
                                 }
                                 _ => log.warning(&format!(
                                     "Target {} not found and ignored",
@@ -281,7 +429,7 @@ impl GenBlockTup {
                                     dep.script_path(),
                                     dep.script_line
                                 ));
-                                return true;
+                                return Ok(true);
                             }
                             let p1 =
                                 process_template_value(log, &dep_block.params[0], &dep, prev_res);
@@ -292,14 +440,68 @@ impl GenBlockTup {
                             if self.search_up(CWD).is_none() {
                                 log.warning("No CWD set")
                             }
-                            return exec_anynewer(self, &p1, &p2);
+                            let res = exec_anynewer(self, &p1, &p2);
+                            if log.explain {
+                                let (input, output) = anynewer_evidence(self, &p1, &p2);
+                                log.explain(&format!(
+                                    "dependency anynewer({p1}, {p2}) at {}:{} is {res}, the newest input {input}, the newest output {output}",
+                                    dep.script_path(),
+                                    dep.script_line
+                                ));
+                            }
+                            return Ok(res);
+                        }
+                        "changed" => {
+                            if dep_block.params.len() != 2 {
+                                log.error(&format!(
+                                    "Dependency 'changed' requires two parameters, but specified {} at {}:{}: ",
+                                    dep_block.params.len(),
+                                    dep.script_path(),
+                                    dep.script_line
+                                ));
+                                return Ok(true);
+                            }
+                            let p1 =
+                                process_template_value(log, &dep_block.params[0], &dep, prev_res);
+                            let p2 =
+                                process_template_value(log, &dep_block.params[1], &dep, prev_res);
+                            log.debug(&format!("changed dep parameters: {}, {}", p1, p2));
+                            let (res, evidence) = exec_changed(self, &p1, &p2);
+                            log.explain(&format!(
+                                "dependency changed({p1}, {p2}) at {}:{} is {res}, {evidence}",
+                                dep.script_path(),
+                                dep.script_line
+                            ));
+                            return Ok(res);
+                        }
+                        "depinfo" => {
+                            if dep_block.params.len() != 2 {
+                                log.error(&format!(
+                                    "Dependency 'depinfo' requires two parameters, but specified {} at {}:{}: ",
+                                    dep_block.params.len(),
+                                    dep.script_path(),
+                                    dep.script_line
+                                ));
+                                return Ok(true);
+                            }
+                            let p1 =
+                                process_template_value(log, &dep_block.params[0], &dep, prev_res);
+                            let p2 =
+                                process_template_value(log, &dep_block.params[1], &dep, prev_res);
+                            log.debug(&format!("depinfo dep parameters: {}, {}", p1, p2));
+                            let (res, evidence) = exec_depinfo(self, &p1, &p2);
+                            log.explain(&format!(
+                                "dependency depinfo({p1}, {p2}) at {}:{} is {res}, {evidence}",
+                                dep.script_path(),
+                                dep.script_line
+                            ));
+                            return Ok(res);
+                        }
+                        name => {
+                            return Err(self.script_failure(dep.script_error(format!(
+                                "Function {name} can't be used as a dependency"
+                            ))));
                         }
-                        _ => todo!(
-                            "function: {:?} at {}:{}: ",
-                            dep_block.name,
-                            dep.script_path(),
-                            &dep.script_line
-                        ),
                     }
                 }
                 BlockType::Eq => {
@@ -310,7 +512,10 @@ impl GenBlockTup {
                         let r1: Option<VarVal> = match p1_block.block_type {
                             BlockType::Function => p1.exec_fun(log, &p1_block, prev_res),
                             _ => {
-                                todo!("block: {:?}", p1_block.block_type)
+                                return Err(self.script_failure(p1_block.script_error(format!(
+                                    "{:?} block can't be compared in eq",
+                                    p1_block.block_type
+                                ))));
                             }
                         };
                         let r2: Option<VarVal> = if len == 2 {
@@ -319,7 +524,10 @@ impl GenBlockTup {
                             match p2_block.block_type {
                                 BlockType::Function => p2.exec_fun(log, &p2_block, prev_res),
                                 _ => {
-                                    todo!("block: {:?}", p2_block.block_type);
+                                    return Err(self.script_failure(p2_block.script_error(format!(
+                                        "{:?} block can't be compared in eq",
+                                        p2_block.block_type
+                                    ))));
                                 }
                             }
                         } else {
@@ -327,14 +535,21 @@ impl GenBlockTup {
                         };
                         //println!("comparing: {:?} and {:?}", r1, r2);
                         log.debug(&format!("comparing: {:?} and {:?}", r1, r2));
+                        log.explain(&format!(
+                            "dependency eq at {}:{} compares {} and {}",
+                            dep.script_path(),
+                            dep.script_line,
+                            r1.as_ref().map_or("none".to_string(), |r1| format!("'{}'", r1.value)),
+                            r2.as_ref().map_or("none".to_string(), |r2| format!("'{}'", r2.value))
+                        ));
                         match r1 {
                             None => match r2 {
-                                None => return true,
-                                _ => return false,
+                                None => return Ok(true),
+                                _ => return Ok(false),
                             },
                             Some(r1) => match r2 {
-                                None => return false,
-                                Some(r2) => return r1.value == r2.value,
+                                None => return Ok(false),
+                                Some(r2) => return Ok(r1.same(&r2)),
                             },
                         }
                         // return r1 == r2;
@@ -349,17 +564,28 @@ impl GenBlockTup {
                             .unwrap_or(VarVal::from_bool(false))
                             .is_true()
                         {
-                            return true;
+                            log.explain(&format!(
+                                "dependency or at {}:{} is true by its block at line {}",
+                                dep.script_path(),
+                                dep.script_line,
+                                child.borrow().script_line
+                            ));
+                            return Ok(true);
                         }
                     }
-                    return false;
+                    log.explain(&format!(
+                        "dependency or at {}:{} is false, no block is true",
+                        dep.script_path(),
+                        dep.script_line
+                    ));
+                    return Ok(false);
+                }
+                _ => {
+                    return Err(self.script_failure(dep.script_error(format!(
+                        "The operation {:?} isn't supported in a dependency",
+                        dep_block.block_type
+                    ))));
                 }
-                _ => todo!(
-                    "the operation {:?} isn't supported yet at {}:{}: ",
-                    dep_block.block_type,
-                    dep.script_path(),
-                    dep.script_line
-                ),
             }
         } else {
             log.error(&format!(
@@ -369,7 +595,29 @@ impl GenBlockTup {
                 dep.script_line
             ))
         }
-        false
+        Ok(false)
+    }
+
+    /// name of the target containing the block
+    pub fn target_name(&self) -> String {
+        let mut curr = Some(self.clone());
+        while let Some(block) = curr {
+            if block.borrow().block_type == BlockType::Target {
+                return block.borrow().name.clone().unwrap_or_default();
+            }
+            curr = block.parent()
+        }
+        String::new()
+    }
+
+    /// a failure of the target containing the block, caused by the script error
+    fn script_failure(&self, err: ScriptError) -> TargetFailure {
+        let reason = err.to_string();
+        report_script_error(err);
+        TargetFailure {
+            target: self.target_name(),
+            reason,
+        }
     }
 
     pub fn get_top_block(&self) -> GenBlockTup {
@@ -403,11 +651,19 @@ impl GenBlockTup {
         let block_type = &self.borrow().block_type.clone();
         log.debug(&format!("processing block of {:?}", block_type));
         match block_type {
-            BlockType::Scope | BlockType::Then | BlockType::Else | BlockType::Choice => {
+            BlockType::Scope
+            | BlockType::Then
+            | BlockType::Else
+            | BlockType::Choice
+            | BlockType::Catch
+            | BlockType::Finally => {
                 let mut res = prev_res.clone();
                 let children = &self.0.borrow().children.clone();
                 for child in children {
-                    res = child.exec(log, &res)
+                    res = child.exec(log, &res);
+                    if interrupted() {
+                        break;
+                    }
                 }
                 res
             }
@@ -419,12 +675,18 @@ impl GenBlockTup {
                     if child_nak.block_type == BlockType::Function
                         && child_nak.name == Some("include".into())
                         || child_nak.block_type == BlockType::Target
+                        || child_nak.block_type == BlockType::Fun
                     {
                         continue;
                     }
                     drop(child_nak);
                     res = child.exec(log, &res);
+                    if interrupted() {
+                        break;
+                    }
                 }
+                // return() or stop() ends the script code out of targets
+                take_jump();
                 res
             }
             BlockType::If => {
@@ -432,38 +694,91 @@ impl GenBlockTup {
                 let children = &naked_block.children;
                 let mut res = children[0].exec(log, prev_res);
                 log.debug(&format!("if cond evaluated as {:?}", res));
+                if interrupted() {
+                    return res;
+                }
                 if res.as_ref().unwrap_or(&VarVal::from_bool(false)).is_true() {
-                    if children[1].borrow().block_type == BlockType::Then {
+                    if children.len() > 1 && children[1].borrow().block_type == BlockType::Then {
                         res = children[1].exec(log, prev_res)
                     }
-                } else if children.len() == 2 && children[1].borrow().block_type == BlockType::Else
-                {
-                    res = children[1].exec(log, prev_res)
-                } else if children.len() == 3 && children[2].borrow().block_type == BlockType::Else
-                {
-                    res = children[2].exec(log, prev_res)
+                } else {
+                    // elif blocks are checked in order, until a condition is true
+                    for branch in children.iter().skip(1) {
+                        let branch_type = branch.borrow().block_type.clone();
+                        match branch_type {
+                            BlockType::Elif => {
+                                let branch_block = branch.borrow();
+                                let Some(cond) = branch_block.children.first() else {
+                                    continue;
+                                };
+                                let cond_res = cond.exec(log, prev_res);
+                                log.debug(&format!("elif cond evaluated as {:?}", cond_res));
+                                if interrupted() {
+                                    return cond_res;
+                                }
+                                if cond_res.as_ref().is_some_and(|cond_res| cond_res.is_true()) {
+                                    if let Some(then) = branch_block.children.get(1)
+                                        && then.borrow().block_type == BlockType::Then
+                                    {
+                                        res = then.exec(log, prev_res)
+                                    }
+                                    break;
+                                }
+                            }
+                            BlockType::Else => {
+                                res = branch.exec(log, prev_res);
+                                break;
+                            }
+                            _ => (),
+                        }
+                    }
                 }
-                if children.len() > 3 {
+                let unexpected = children
+                    .iter()
+                    .enumerate()
+                    .skip(1)
+                    .filter(|(i, child)| match child.borrow().block_type {
+                        BlockType::Then => *i != 1,
+                        BlockType::Elif | BlockType::Else => false,
+                        _ => true,
+                    })
+                    .count();
+                if unexpected > 0 {
                     log.error(&format!(
                         "Unexpected block(s) {} at {}:{}: ",
-                        children.len(),
+                        unexpected,
                         naked_block.script_path(),
                         naked_block.script_line
                     ))
                 }
                 res
             }
+            // a definition is executed only when the function is called
+            BlockType::Fun => prev_res.clone(),
             BlockType::Function => {
                 let naked_block = self.borrow();
                 log.debug(&format!("function; {:?}", naked_block.name));
                 for param in &naked_block.params {
                     log.debug(&format!("parameter; {}", param))
                 }
-                self.exec_fun(log, &naked_block, prev_res)
+                let res = self.exec_fun(log, &naked_block, prev_res);
+                // the innermost failed function is reported to 'catch'
+                if failure_pending() {
+                    let mut origin = FAILURE_ORIGIN.write().unwrap();
+                    if origin.is_none() {
+                        *origin = Some((
+                            naked_block.name.clone().unwrap_or_default(),
+                            format!("{}:{}", naked_block.script_path(), naked_block.script_line),
+                        ))
+                    }
+                }
+                res
             }
+            BlockType::Try => self.exec_try(log, prev_res),
             BlockType::For => {
                 let mut res = prev_res.clone();
                 let mut range = Vec::new();
+                let mut map_values = None; // values of keys when a map is iterated
                 let naked_block = self.borrow();
                 let Some(name) = &naked_block.name.clone() else {
                     log.error(&format!(
@@ -491,12 +806,23 @@ impl GenBlockTup {
                     return None;
                 }
                 let range_as_var = self.prev_or_search_up(range_as_opt, prev_res);
-
-                if let Some(range_as_val) = range_as_var {
-                    if range_as_val.val_type == VarType::Array {
-                        for var_el in range_as_val.values {
-                            range.push(var_el.clone())
-                        }
+                // for var:from..to
+                let numbers = if naked_block.params.is_empty() && range_as_var.is_none() {
+                    numeric_range(&process_template_value(log, range_as_opt, &naked_block, prev_res))
+                } else {
+                    None
+                };
+                if !naked_block.params.is_empty() {
+                    range = self.function_range(log, &naked_block, range_as_opt, prev_res)?
+                } else if let Some(numbers) = numbers {
+                    range = numbers
+                } else if let Some(range_as_val) = range_as_var {
+                    if range_as_val.is_array() {
+                        range = range_as_val.into_values()
+                    } else if let Value::Map(entries) = range_as_val.value {
+                        let (keys, values) = entries.into_iter().unzip();
+                        range = keys;
+                        map_values = Some(values)
                     } else {
                         let Some(sep_can) = &naked_block.flex.clone() else {
                             log.error(&format!(
@@ -509,12 +835,12 @@ impl GenBlockTup {
                         let sep_var = self.search_up(sep_can);
                         let sep_val = match sep_var {
                             None => sep_can,
-                            Some(val) => &val.value.clone(),
+                            Some(val) => &val.text(),
                         };
                         // expand template variables
                         let range_as_val = process_template_value(
                             log,
-                            &range_as_val.value,
+                            &range_as_val.text(),
                             &naked_block,
                             prev_res,
                         );
@@ -534,34 +860,52 @@ impl GenBlockTup {
                     let sep_var = self.search_up(sep_can);
                     let sep_val = match sep_var {
                         None => sep_can,
-                        Some(val) => &val.value.clone(),
+                        Some(val) => &val.text(),
                     };
                     let values = range_as_opt.split(sep_val);
                     for var_el in values {
                         range.push(var_el.to_string())
                     }
                 }
+                // for key,value:map
+                let (name, value_name) = match name.split_once(',') {
+                    Some((name, value_name)) => (name.trim(), Some(value_name.trim())),
+                    None => (name.as_str(), None),
+                };
+                let map_values = match (map_values, value_name) {
+                    (Some(map_values), Some(_)) => map_values,
+                    (_, Some(_)) => {
+                        log.error(&format!(
+                            "A 'for' with a key and a value requires a map range at {}:{}: ",
+                            naked_block.script_path(),
+                            naked_block.script_line
+                        ));
+                        return None;
+                    }
+                    _ => Vec::new(),
+                };
                 let children = &naked_block.children.clone();
                 drop(naked_block);
                 for (index, element) in range.iter().enumerate() {
-                    let var_element = VarVal {
-                        val_type: VarType::Generic,
-                        value: element.clone(),
-                        values: Vec::new(),
-                    };
-                    let var_index = VarVal {
-                        val_type: VarType::Number,
-                        value: format!("{}", index),
-                        values: Vec::new(),
-                    };
+                    let var_element = VarVal::from_string(element);
+                    let var_index = VarVal::from_i64(index as i64);
                     {
                         let mut naked_block = self.0.borrow_mut();
                         naked_block.vars.insert(name.to_string(), var_element);
+                        if let Some(value_name) = value_name {
+                            naked_block.vars.insert(value_name.to_string(), VarVal::from_string(&map_values[index]));
+                        }
                         naked_block.vars.insert("~index~".to_string(), var_index);
                     }
 
                     for child in children {
-                        res = child.exec(log, &res)
+                        res = child.exec(log, &res);
+                        if interrupted() {
+                            break;
+                        }
+                    }
+                    if !loop_continues() {
+                        return res;
                     }
                 }
                 res
@@ -611,11 +955,10 @@ impl GenBlockTup {
                 let children = &naked_block.children;
                 let len = children.len();
                 if len < 1 {
-                    log.error(&format!(
-                        "At least one argument has to be specified in eq at {}:{}: ",
-                        naked_block.script_path(),
-                        naked_block.script_line
-                    ))
+                    report_script_error(
+                        naked_block.script_error("At least one argument has to be specified in eq"),
+                    );
+                    return None;
                 }
 
                 let mut before_res = children[0].exec(log, prev_res);
@@ -630,7 +973,7 @@ impl GenBlockTup {
                         Some(ref res_some) => match before_res {
                             None => return Some(VarVal::from_bool(false)),
                             Some(ref before_some) => {
-                                if before_some.value != res_some.value {
+                                if !before_some.same(res_some) {
                                     return Some(VarVal::from_bool(false));
                                 }
                             }
@@ -639,18 +982,17 @@ impl GenBlockTup {
 
                     before_res = res
                 }
-                Some(VarVal::from_bool(before_res.is_none()))
+                Some(VarVal::from_bool(len > 1 || before_res.is_none()))
             }
             BlockType::Neq => {
                 let naked_block = self.0.borrow();
                 let children = &naked_block.children;
                 let len = children.len();
                 if len < 1 {
-                    log.error(&format!(
-                        "At least one argument has to be specified in neq at {}:{}: ",
-                        naked_block.script_path(),
-                        naked_block.script_line
-                    ))
+                    report_script_error(
+                        naked_block.script_error("At least one argument has to be specified in neq"),
+                    );
+                    return None;
                 }
                 let first = children[0].exec(log, prev_res);
                 if len > 1 {
@@ -658,7 +1000,7 @@ impl GenBlockTup {
                         let current = child.exec(log, prev_res);
                         match (&current, &first) {
                             (Some(current), Some(first)) => {
-                                if current.value == first.value {
+                                if current.same(first) {
                                     continue;
                                 }
                             }
@@ -690,7 +1032,13 @@ impl GenBlockTup {
                 let mut val = control_var.unwrap().is_true();
                 while val {
                     for child in &children {
-                        res = child.exec(log, &res)
+                        res = child.exec(log, &res);
+                        if interrupted() {
+                            break;
+                        }
+                    }
+                    if !loop_continues() {
+                        return res;
                     }
                     let control_var = self.search_up(&control); // will be always found
                     val = control_var.unwrap().is_true()
@@ -705,20 +1053,7 @@ impl GenBlockTup {
                 if let Some(var) = self.search_up(&control) {
                     let children = &naked_block.children.clone();
                     let mut chosen = false;
-                    let var = match var.val_type {
-                        VarType::Environment => match env::var(&var.value) {
-                            Ok(val) => val,
-                            Err(_e) => var.value,
-                        },
-                        VarType::Property => {
-                            if let Some(val) = get_property(&var.value) {
-                                val
-                            } else {
-                                var.value
-                            }
-                        }
-                        _ => var.value,
-                    };
+                    let var = var.resolved().to_string();
                     for child in children {
                         //println!{"case {:?} / {}", child.borrow().name, var}
                         // TODO there is no check that else is the final choice, perhaps add it in the future
@@ -739,6 +1074,9 @@ impl GenBlockTup {
                                 break;
                             }
                         }
+                        if interrupted() {
+                            break;
+                        }
                     }
                 } else {
                     log.error(&format!(
@@ -752,13 +1090,11 @@ impl GenBlockTup {
             }
             _ => {
                 let naked_block = self.borrow();
-                todo!(
-                    "not implemented block: {:?}, {:?} at {}:{}: ",
-                    naked_block.block_type,
-                    &naked_block.name,
-                    naked_block.script_path(),
-                    &naked_block.script_line
-                )
+                report_script_error(naked_block.script_error(format!(
+                    "{:?} block can't be executed here",
+                    naked_block.block_type
+                )));
+                None
             }
         }
     }
@@ -774,7 +1110,7 @@ impl GenBlockTup {
             let len = fun_block.params.len();
             for i in 1..len {
                 if write!(file, "{}", self.parameter(log, i, fun_block, res_prev)).is_err() {
-                    log.error(&format!{"Writing in {} failed at {}:{}: ", fname, fun_block.script_path(), fun_block.script_line});
+                    function_error(log, format!{"Writing in {} failed at {}:{}: ", fname, fun_block.script_path(), fun_block.script_line});
                     break;
                 }
             }
@@ -786,11 +1122,10 @@ impl GenBlockTup {
         };
         match name {
             "display" => {
-                println!(
-                    "{}",
-                    util::insert_ctrl_char(*self.parameter(log, 0, fun_block, res_prev))
-                );
+                let msg = util::insert_ctrl_char(*self.parameter(log, 0, fun_block, res_prev));
+                println!("{}", msg);
                 io::stdout().flush().unwrap();
+                log.tee(&msg);
                 if fun_block.params.len() > 1 {
                     log.error(&format!{"Display parameters are ignored after first one at {}:{}: ", fun_block.script_path(), fun_block.script_line})
                 }
@@ -811,13 +1146,13 @@ impl GenBlockTup {
                 if !has_root(&fname)
                     && let Some(cwd) = fun_block.search_up(CWD)
                 {
-                    fname = cwd.value + MAIN_SEPARATOR_STR + &fname
+                    fname = cwd.into_text() + MAIN_SEPARATOR_STR + &fname
                 }
                 let file = File::create(&fname);
                 if let Ok(mut file) = file {
                     write_lambda(&mut file, &fname)
                 } else {
-                    log.error(&format!{"File {} can't be opened for writing at {}:{}: ", fname, fun_block.script_path(), fun_block.script_line})
+                    function_error(log, format!{"File {} can't be opened for writing at {}:{}: ", fname, fun_block.script_path(), fun_block.script_line})
                 }
             }
             "writex" if cfg!(not(unix)) => {
@@ -825,13 +1160,13 @@ impl GenBlockTup {
                 if !has_root(&fname)
                     && let Some(cwd) = fun_block.search_up(CWD)
                 {
-                    fname = cwd.value + MAIN_SEPARATOR_STR + &fname
+                    fname = cwd.into_text() + MAIN_SEPARATOR_STR + &fname
                 }
                 let file = File::create(&fname);
                 if let Ok(mut file) = file {
                     write_lambda(&mut file, &fname)
                 } else {
-                    log.error(&format!{"File {} can't be opened for writing at {}:{}: ", fname, fun_block.script_path(), fun_block.script_line})
+                    function_error(log, format!{"File {} can't be opened for writing at {}:{}: ", fname, fun_block.script_path(), fun_block.script_line})
                 }
             }
             "writea" => {
@@ -839,7 +1174,7 @@ impl GenBlockTup {
                 if !has_root(&fname)
                     && let Some(cwd) = fun_block.search_up(CWD)
                 {
-                    fname = cwd.value + MAIN_SEPARATOR_STR + &fname
+                    fname = cwd.into_text() + MAIN_SEPARATOR_STR + &fname
                 }
                 if let Ok(mut file) = OpenOptions::new()
                     .read(true)
@@ -849,7 +1184,7 @@ impl GenBlockTup {
                 {
                     write_lambda(&mut file, &fname)
                 } else {
-                    log.error(&format!{"File {} can't be opened for writing at {}:{}: ", fname, fun_block.script_path(), fun_block.script_line})
+                    function_error(log, format!{"File {} can't be opened for writing at {}:{}: ", fname, fun_block.script_path(), fun_block.script_line})
                 }
             }
             #[cfg(unix)]
@@ -858,7 +1193,7 @@ impl GenBlockTup {
                 if !has_root(&fname)
                     && let Some(cwd) = fun_block.search_up(CWD)
                 {
-                    fname = cwd.value + MAIN_SEPARATOR_STR + &fname
+                    fname = cwd.into_text() + MAIN_SEPARATOR_STR + &fname
                 }
                 match OpenOptions::new()
                     .create(true)
@@ -867,7 +1202,7 @@ impl GenBlockTup {
                     .mode(0o700)
                     .open(&fname) {
                     Ok(mut file) => write_lambda(&mut file, &fname),
-                    Err(_) => log.error(&format!{"File {} can't be opened for writing at {}:{}: ", fname, fun_block.script_path(), fun_block.script_line}),
+                    Err(_) => function_error(log, format!{"File {} can't be opened for writing at {}:{}: ", fname, fun_block.script_path(), fun_block.script_line}),
                 }
             }
             "assign" => return self.exec_assign(log, fun_block, res_prev),
@@ -879,8 +1214,8 @@ impl GenBlockTup {
                 ));
 
                 return Some(VarVal::from_bool(
-                    self.parameter(log, 0, fun_block, res_prev)
-                        != self.parameter(log, 1, fun_block, res_prev),
+                    !VarVal::from_string(*self.parameter(log, 0, fun_block, res_prev))
+                        .same(&VarVal::from_string(*self.parameter(log, 1, fun_block, res_prev))),
                 ));
             }
             "eq" => {
@@ -891,15 +1226,17 @@ impl GenBlockTup {
                     self.parameter(log, 1, fun_block, res_prev)
                 ));
                 return Some(VarVal::from_bool(
-                    self.parameter(log, 0, fun_block, res_prev)
-                        == self.parameter(log, 1, fun_block, res_prev),
+                    VarVal::from_string(*self.parameter(log, 0, fun_block, res_prev))
+                        .same(&VarVal::from_string(*self.parameter(log, 1, fun_block, res_prev))),
                 ));
             }
-            "exec" | "aexec" => {
+            "exec" | "exec?" | "aexec" => {
+                // exec? tolerates a non zero exit code
+                let tolerated = name == "exec?";
                 let mut exec: String = fun_block.flex.as_ref()?.to_string();
                 // look for var first
                 if let Some(exec1) = fun_block.search_up(&exec) {
-                    exec = *process_template_value(log, &exec1.value, fun_block, res_prev);
+                    exec = *process_template_value(log, &exec1.text(), fun_block, res_prev);
                 }
                 let mut params: Vec<_> = Vec::new();
                 for i in 0..fun_block.params.len() {
@@ -911,16 +1248,15 @@ impl GenBlockTup {
                         fun_block.params, val
                     ));
                     if let Some(param) = val {
-                        if !param.values.is_empty() {
-                            // array
-                            for param in param.values {
+                        if param.is_array() {
+                            for param in param.into_values() {
                                 params
                                     .push(*process_template_value(log, &param, fun_block, res_prev))
                             }
-                        } else if param.val_type != VarType::Array {
+                        } else {
                             params.push(*process_template_value(
                                 log,
-                                &param.value,
+                                &param.text(),
                                 fun_block,
                                 res_prev,
                             ))
@@ -937,7 +1273,7 @@ impl GenBlockTup {
                         let mut work_dir = match fun_block.search_up(work_dir_val) {
                             Some(work_dir_val1) => *process_template_value(
                                 log,
-                                &work_dir_val1.value,
+                                &work_dir_val1.text(),
                                 fun_block,
                                 res_prev,
                             ),
@@ -948,7 +1284,7 @@ impl GenBlockTup {
                             let cwd = fun_block.search_up(CWD);
                             //println!{"found cwd {cwd:?}"}
                             if let Some(cwd) = cwd {
-                                work_dir = cwd.value + std::path::MAIN_SEPARATOR_STR + &work_dir
+                                work_dir = cwd.into_text() + std::path::MAIN_SEPARATOR_STR + &work_dir
                             }
                         }
                         let path = Path::new(&work_dir);
@@ -964,7 +1300,7 @@ impl GenBlockTup {
                     // take it from the target cwd
                     let work_dir = fun_block.search_up(CWD);
                     if let Some(work_dir) = work_dir {
-                        cwd = work_dir.value
+                        cwd = work_dir.into_text()
                     }
                 }
 
@@ -991,7 +1327,9 @@ impl GenBlockTup {
                     if let Ok(status) = status {
                         return Some(VarVal::from_i32(status.id() as i32));
                     }
-                    log.error(&format!("Command {} with {:?} in {} failed to start asynchronically at {}:{}: , reason {}", exec, params, cwd, fun_block.script_path(), fun_block.script_line, status.err()?))
+                    let msg = format!("Command {} with {:?} in {} failed to start asynchronically at {}:{}: , reason {}", exec, params, cwd, fun_block.script_path(), fun_block.script_line, status.err()?);
+                    log.error(&msg);
+                    report_failure(msg)
                 } else if fun_block.out.is_some() {
                     let output = if cwd.is_empty() {
                         Command::new(&exec)
@@ -1007,7 +1345,7 @@ impl GenBlockTup {
                     };
                     // command is always async, simply output is waiting
                     let Ok(output) = output else {
-                        log.error(&format!(
+                        let msg = format!(
                             "Command {} with {:?} in {} failed to start at {}:{}: , reason {:?}",
                             exec,
                             params,
@@ -1015,62 +1353,100 @@ impl GenBlockTup {
                             fun_block.script_path(),
                             fun_block.script_line,
                             output.err()
-                        ));
+                        );
+                        log.error(&msg);
+                        report_failure(msg);
                         return None;
                     };
-                    // TODO more error handling
-                    if output.status.success() {
-                        let stdout = String::from_utf8_lossy(&output.stdout);
-                        let parent_block = fun_block.parent.clone()?;
+                    // the output is kept even when the command fails, inside 'try' the block enclosing it
+                    // gets it, so it can be examined in 'catch'
+                    let out = fun_block.out.clone()?;
+                    let stdout = String::from_utf8_lossy(&output.stdout);
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    let code = output.status.code();
+                    let signal = exit_signal(&output.status);
+                    // the output goes to the variables instead of the console, but a log file gets it too
+                    for line in stdout.lines().chain(stderr.lines()) {
+                        log.tee(line)
+                    }
+                    {
+                        let parent_block = fun_block.parent.clone()?.outside_try();
                         let mut parent_block_mut = parent_block.borrow_mut();
-                        parent_block_mut
-                            .vars
-                            .insert(fun_block.out.clone()?, VarVal::from_string(stdout.trim()));
-
-                        return Some(VarVal::from_i32(output.status.code()?));
+                        let vars = &mut parent_block_mut.vars;
+                        vars.insert(out.clone(), VarVal::from_string(stdout.trim()));
+                        vars.insert(format!("{out}.lines"), VarVal::from_iter(stdout.lines()));
+                        vars.insert(format!("{out}.stderr"), VarVal::from_string(stderr.trim()));
+                        vars.insert(format!("{out}.code"), code.map_or(VarVal::from_string(""), VarVal::from_i32));
+                        vars.insert(format!("{out}.signal"), signal.map_or(VarVal::from_string(""), VarVal::from_i32));
+                    }
+                    if output.status.success() || tolerated && code.is_some() {
+                        return Some(VarVal::from_i32(code?));
                     } else {
-                        log.error(&format!(
-                            "Command {} with {:?} in {} failed to start at {}:{}: , reason {}",
+                        let reason = match signal {
+                            Some(signal) => format!("terminated by signal {signal}, {stderr}"),
+                            None => stderr.to_string(),
+                        };
+                        let msg = format!(
+                            "Command {} with {:?} in {} failed at {}:{}: , reason {}",
                             exec,
                             params,
                             cwd,
                             fun_block.script_path(),
                             fun_block.script_line,
-                            String::from_utf8_lossy(&output.stderr)
-                        ))
+                            reason
+                        );
+                        log.error(&msg);
+                        report_failure(msg)
                     }
                 } else {
-                    let status = if cwd.is_empty() {
-                        Command::new(&exec)
-                            .args(&params)
-                            .envs(crate::get_properties())
-                            .status()
+                    let mut command = Command::new(&exec);
+                    command.args(&params).envs(crate::get_properties());
+                    if !cwd.is_empty() {
+                        command.current_dir(&cwd);
+                    }
+                    let status = if log.is_teed() {
+                        status_teed(log, &mut command)
                     } else {
-                        Command::new(&exec)
-                            .envs(crate::get_properties())
-                            .current_dir(&cwd)
-                            .args(&params)
-                            .status()
+                        command.status()
                     };
                     match status {
                         Ok(status) => match status.code() {
-                            Some(code) => return Some(VarVal::from_i32(code)),
+                            Some(code) => {
+                                if code != 0 && !tolerated {
+                                    report_failure(format!(
+                                        "Command {} exited with code {} at {}:{}",
+                                        exec,
+                                        code,
+                                        fun_block.script_path(),
+                                        fun_block.script_line
+                                    ))
+                                }
+                                return Some(VarVal::from_i32(code));
+                            }
                             //self.parent().unwrap().add_var("~~".to_string(), VarVal{val_type: VarType::Number, value: code.to_string(), values: Vec::new()});},
-                            None => log.error(&format!(
-                                "The process terminated by signal at {:?}:{}",
-                                fun_block.script_path(),
-                                fun_block.script_line
-                            )),
+                            None => {
+                                let msg = format!(
+                                    "The process terminated by signal at {:?}:{}",
+                                    fun_block.script_path(),
+                                    fun_block.script_line
+                                );
+                                log.error(&msg);
+                                report_failure(msg)
+                            }
                         },
-                        Err(err) => log.error(&format!(
-                            "Command {} with {:?} in {} failed to start at {:?}:{}, reason {}",
-                            exec,
-                            params,
-                            cwd,
-                            fun_block.script_path(),
-                            fun_block.script_line,
-                            err
-                        )),
+                        Err(err) => {
+                            let msg = format!(
+                                "Command {} with {:?} in {} failed to start at {:?}:{}, reason {}",
+                                exec,
+                                params,
+                                cwd,
+                                fun_block.script_path(),
+                                fun_block.script_line,
+                                err
+                            );
+                            log.error(&msg);
+                            report_failure(msg)
+                        }
                     }
                 }
             }
@@ -1164,7 +1540,7 @@ impl GenBlockTup {
                     if !has_root(&fname)
                         && let Some(cwd) = fun_block.search_up(CWD)
                     {
-                        fname = cwd.value + MAIN_SEPARATOR_STR + &fname
+                        fname = cwd.into_text() + MAIN_SEPARATOR_STR + &fname
                     }
                     if fun_block.params.len() == 2 {
                         let time_str = *self.parameter(log, 1, fun_block, res_prev);
@@ -1189,8 +1565,8 @@ impl GenBlockTup {
                             file.set_modified(time).ok();
                         }
                     }
-                    let timestamp = timestamp(&fname)?;
-                    return Some(VarVal::from_string(&timestamp));
+                    let time = fs::metadata(&fname).ok()?.modified().ok()?;
+                    return Some(VarVal::from_date(time));
                 }
             }
             "cropname" => {
@@ -1206,7 +1582,7 @@ impl GenBlockTup {
                     return Some(VarVal::from_string(fname));
                 }
                 if !has_root(&fname) {
-                    fname = cwd.value.clone() + MAIN_SEPARATOR_STR + &fname
+                    fname = cwd.text() + MAIN_SEPARATOR_STR + &fname
                 }
                 let mut mask = *self.parameter(log, 1, fun_block, res_prev);
                 let crop_end = mask.starts_with("*");
@@ -1234,7 +1610,7 @@ impl GenBlockTup {
                         String::new()
                     };
                     if !has_root(&mask) {
-                        mask = cwd.value + MAIN_SEPARATOR_STR + &mask
+                        mask = cwd.into_text() + MAIN_SEPARATOR_STR + &mask
                     }
                     if fname.starts_with(&mask)
                         && (end_mask.is_empty() || fname.ends_with(&end_mask))
@@ -1267,13 +1643,13 @@ impl GenBlockTup {
                 if !has_root(&fname)
                     && let Some(cwd) = fun_block.search_up(CWD)
                 {
-                    fname = cwd.value + MAIN_SEPARATOR_STR + &fname
+                    fname = cwd.into_text() + MAIN_SEPARATOR_STR + &fname
                 }
                 let file_content = &fs::read_to_string(&fname).ok();
                 return match file_content {
                     Some(content) => Some(VarVal::from_string(content)),
                     None => {
-                        log.error(&format!{"File {} can't be opened for reading or a read error at {}:{}: ", fname, fun_block.script_path(), fun_block.script_line});
+                        function_error(log, format!{"File {} can't be opened for reading or a read error at {}:{}: ", fname, fun_block.script_path(), fun_block.script_line});
                         None
                     }
                 };
@@ -1283,7 +1659,7 @@ impl GenBlockTup {
                 if !has_root(&path)
                     && let Some(cwd) = fun_block.search_up(CWD)
                 {
-                    path = cwd.value + MAIN_SEPARATOR_STR + &path
+                    path = cwd.into_text() + MAIN_SEPARATOR_STR + &path
                 }
                 #[cfg(any(unix, target_os = "redox"))]
                 if name == "canonicalize"
@@ -1325,15 +1701,18 @@ impl GenBlockTup {
                 let mut dir1 = dir1?;
                 if let Some(cwd) = fun_block.search_up(CWD) {
                     if !has_root(&dir1) {
-                        dir1 = cwd.value.clone() + MAIN_SEPARATOR_STR + &dir1
+                        dir1 = cwd.text() + MAIN_SEPARATOR_STR + &dir1
                     }
                     if let Some(ref dir2v) = dir2
                         && !has_root(dir2v)
                     {
-                        dir2 = Some(cwd.value + MAIN_SEPARATOR_STR + dir2v)
+                        dir2 = Some(cwd.into_text() + MAIN_SEPARATOR_STR + dir2v)
                     }
                 }
                 log.debug(&format! {"newerthen: {:?}/{:?} then {:?}/{:?}", dir1, ext1, dir2, ext2});
+                if let Some(ext1) = &ext1 {
+                    watch_file(&format!("{dir1}{MAIN_SEPARATOR}*{ext1}"))
+                }
                 return Some(VarVal::from_vec(find_newer(&dir1, &ext1?, &dir2, &ext2)));
             }
             "anynewer" => {
@@ -1344,15 +1723,38 @@ impl GenBlockTup {
                     && let Some(cwd) = fun_block.search_up(CWD)
                 {
                     if !has_root(&p1) {
-                        p1 = cwd.value.clone() + MAIN_SEPARATOR_STR + &p1
+                        p1 = cwd.text() + MAIN_SEPARATOR_STR + &p1
                     }
                     if !has_root(&p2) {
-                        p2 = cwd.value + MAIN_SEPARATOR_STR + &p2
+                        p2 = cwd.into_text() + MAIN_SEPARATOR_STR + &p2
                     }
                 }
                 log.debug(&format!("anynewer parameters: {}, {}", p1, p2));
+                watch_file(&p1);
                 return Some(VarVal::from_bool(newest(&p1) > newest(&p2)));
             }
+            "changed" => {
+                if fun_block.params.len() != 2 {
+                    log.error(&format!{"Function 'changed' requires 2 parameters, but specified {} at {}:{}: ", fun_block.params.len(), fun_block.script_path(), fun_block.script_line});
+                    return Some(VarVal::from_bool(true));
+                }
+                let p1 = *self.parameter(log, 0, fun_block, res_prev);
+                let p2 = *self.parameter(log, 1, fun_block, res_prev);
+                let (res, evidence) = exec_changed(self, &p1, &p2);
+                log.debug(&format!("changed({p1}, {p2}): {evidence}"));
+                return Some(VarVal::from_bool(res));
+            }
+            "depinfo" => {
+                if fun_block.params.len() != 2 {
+                    log.error(&format!{"Function 'depinfo' requires 2 parameters, but specified {} at {}:{}: ", fun_block.params.len(), fun_block.script_path(), fun_block.script_line});
+                    return Some(VarVal::from_bool(true));
+                }
+                let p1 = *self.parameter(log, 0, fun_block, res_prev);
+                let p2 = *self.parameter(log, 1, fun_block, res_prev);
+                let (res, evidence) = exec_depinfo(self, &p1, &p2);
+                log.debug(&format!("depinfo({p1}, {p2}): {evidence}"));
+                return Some(VarVal::from_bool(res));
+            }
             "gt" => {
                 if fun_block.params.len() != 2 {
                     log.error(&format!{"Greater than requires 2 parameters, but specified {} at {}:{}: ", fun_block.params.len(), fun_block.script_path(), fun_block.script_line})
@@ -1360,13 +1762,8 @@ impl GenBlockTup {
                     let p1 = *self.parameter(log, 0, fun_block, res_prev);
                     let p2 = *self.parameter(log, 1, fun_block, res_prev);
                     log.debug(&format!("Comparing {} to {} at greater", p1, p2));
-                    // TODO baybe check val_type == Number ?
-                    if let Ok(val) = p1.parse::<f64>()
-                        && let Ok(val2) = p2.parse::<f64>()
-                    {
-                        return Some(VarVal::from_bool(val > val2));
-                    }
-                    return Some(VarVal::from_bool(p1 > p2));
+                    let order = VarVal::from_string(p1).compare(&VarVal::from_string(p2));
+                    return Some(VarVal::from_bool(order == Some(cmp::Ordering::Greater)));
                 }
             }
             "lt" => {
@@ -1376,13 +1773,8 @@ impl GenBlockTup {
                     let p1 = *self.parameter(log, 0, fun_block, res_prev);
                     let p2 = *self.parameter(log, 1, fun_block, res_prev);
                     log.debug(&format!("Comparing {} to {} at littler", p1, p2));
-                    // TODO baybe check val_type == Number ?
-                    if let Ok(val) = p1.parse::<f64>()
-                        && let Ok(val2) = p2.parse::<f64>()
-                    {
-                        return Some(VarVal::from_bool(val < val2));
-                    }
-                    return Some(VarVal::from_bool(p1 < p2));
+                    let order = VarVal::from_string(p1).compare(&VarVal::from_string(p2));
+                    return Some(VarVal::from_bool(order == Some(cmp::Ordering::Less)));
                 }
             }
             "not" => {
@@ -1405,16 +1797,16 @@ impl GenBlockTup {
                 if let Some(param) = param {
                     match param.val_type {
                         VarType::RepositoryRust => {
-                            if let Some(pos) = param.value.find('@') {
+                            if let Some((name, version)) = param.text().split_once('@') {
                                 return Some(VarVal::from_string(format!(
                                     "https://crates.io/api/v1/crates/{}/{}/download",
-                                    &param.value[0..pos],
-                                    &param.value[pos + 1..]
+                                    name, version
                                 )));
                             }
                         }
                         VarType::RepositoryMaven | VarType::Generic => {
-                            let parts = param.value.split(':');
+                            let text = param.text();
+                            let parts = text.split(':');
                             let mav_parts: Vec<_> = parts.collect();
                             //https://repo1.maven.org/maven2/com/baomidou/mybatis-plus-boot-starter/3.5.3.1/mybatis-plus-boot-starter-3.5.3.1.jar
                             return Some(VarVal::from_string(format!(
@@ -1436,7 +1828,8 @@ impl GenBlockTup {
                 if let Some(param) = param {
                     match param.val_type {
                         VarType::RepositoryMaven | VarType::Generic => {
-                            let parts = param.value.split(':');
+                            let text = param.text();
+                            let parts = text.split(':');
                             let parts: Vec<_> = parts.collect();
                             if parts.len() != 3 {
                                 log.error(&format!(
@@ -1465,8 +1858,8 @@ impl GenBlockTup {
                     // destinations as evaluate parameters in a function or a block
                     match fun_block.prev_or_search_up(&fun_block.params[i], res_prev) {
                         Some(param1) => {
-                            if param1.val_type == VarType::Array {
-                                res.extend_from_slice(&param1.values) // consider to massage a value  *process_template_value(log, param1.values[k], &fun_block, res_prev);
+                            if param1.is_array() {
+                                res.extend_from_slice(param1.values()) // consider to massage a value  *process_template_value(log, param1.values[k], &fun_block, res_prev);
                             } else {
                                 res.push(*self.parameter(log, i, fun_block, res_prev))
                             }
@@ -1497,17 +1890,17 @@ impl GenBlockTup {
                 // remove from an array parameter all matching parameters 1..n
                 let param = self.prev_or_search_up(&fun_block.params[0], res_prev);
                 if let Some(param) = param
-                    && param.val_type == VarType::Array
+                    && param.is_array()
                 {
                     // TODO think if filtering should be case insesible on Windows
                     let filter_vals = fun_block.params[1..]
                         .iter()
                         .map(|filter| process_template_value(log, filter, fun_block, res_prev))
                         .collect::<Vec<_>>();
-                    let files = param.values;
+                    let files = param.into_values();
                     let cwd = fun_block
                         .search_up(CWD)
-                        .map(|cwd| cwd.value)
+                        .map(|cwd| cwd.into_text())
                         .unwrap_or_default();
                     let vec = files
                         .into_iter()
@@ -1534,13 +1927,39 @@ impl GenBlockTup {
                     log.error(&format!{"Variable {} not found or not an array at {}:{}: ", fun_block.params[0], fun_block.script_path(), fun_block.script_line})
                 }
             }
+            "break" | "continue" | "return" | "stop" => {
+                if matches!(name, "break" | "continue") && !fun_block.in_loop() {
+                    report_script_error(
+                        fun_block.script_error(format!("{name}() can be used only in a loop")),
+                    );
+                    return None;
+                }
+                let (jump, res) = match name {
+                    "break" => (Jump::Break, res_prev.clone()),
+                    "continue" => (Jump::Continue, res_prev.clone()),
+                    "return" if !no_parameters(fun_block) => {
+                        let res = match fun_block.prev_or_search_up(&fun_block.params[0], res_prev) {
+                            Some(var) if var.is_array() => var,
+                            _ => VarVal::from_string(*self.parameter(log, 0, fun_block, res_prev)),
+                        };
+                        (Jump::Return, Some(res))
+                    }
+                    "return" => (Jump::Return, res_prev.clone()),
+                    _ => (Jump::Stop, res_prev.clone()),
+                };
+                *JUMP.write().unwrap() = Some(jump);
+                return res;
+            }
             "panic" => {
-                panic!(
+                // stops the current target, or the script when called outside of a target
+                let msg = format!(
                     "{} at {}:{}: ",
                     self.parameter(log, 0, fun_block, res_prev),
                     fun_block.script_path(),
                     fun_block.script_line
-                )
+                );
+                log.error(&msg);
+                report_failure(msg)
             }
             "element" => {
                 // the function allows to extract or set an element of an array
@@ -1556,7 +1975,7 @@ impl GenBlockTup {
 
                 let index_param = match self.prev_or_search_up(&fun_block.params[1], res_prev) {
                     None => fun_block.params[1].to_owned(),
-                    Some(val) => val.value.clone(),
+                    Some(val) => val.text(),
                 };
                 let index: usize = index_param.parse().unwrap_or_default();
                 let val = if fun_block.params.len() > 2 {
@@ -1566,21 +1985,71 @@ impl GenBlockTup {
                 };
                 let mut parent_bare = var_block.0.borrow_mut();
                 let var = parent_bare.vars.get_mut(name)?;
-                if var.val_type == VarType::Array {
-                    if var.values.is_empty() || index > var.values.len() - 1 {
+                if let Value::Array(ref mut values) = var.value {
+                    if values.is_empty() || index > values.len() - 1 {
                         log.error(&format!{"Specified index {} is out of bounds {} at {}:{}: ",  index, name, fun_block.script_path(), fun_block.script_line});
                         return None;
                     }
-                    let res = Some(VarVal::from_string(&var.values[index]));
+                    let res = Some(VarVal::from_string(&values[index]));
                     if let Some(val) = val {
                         // set
-                        var.values[index] = val
+                        values[index] = val
                     }
                     return res; // get/set
                 } else {
                     log.error(&format!{"Specified argument {} isn't an array at {}:{}: ",  name, fun_block.script_path(), fun_block.script_line});
                 }
             }
+            "entry" | "remove" => {
+                // the function allows to get, set, or remove a value of a map key
+                if fun_block.params.len() < 2 || (name == "remove" && fun_block.params.len() > 2) {
+                    log.error(&format!{"The '{name}' requires {} parameters, but specified {} at {}:{}: ", if name == "remove" {"2"} else {"2 or 3"}, fun_block.params.len(), fun_block.script_path(), fun_block.script_line}) ;
+                    return None;
+                }
+                let map_name = &fun_block.params[0];
+                let Some(var_block) = fun_block.parent.clone()?.search_up_block(map_name) else {
+                    log.error(&format!{"Specified argument {} wasn't found at {}:{}: ", map_name,  fun_block.script_path(), fun_block.script_line});
+                    return None;
+                };
+                let key = *self.parameter(log, 1, fun_block, res_prev);
+                let val = if fun_block.params.len() > 2 {
+                    Some(*self.parameter(log, 2, fun_block, res_prev))
+                } else {
+                    None
+                };
+                let mut parent_bare = var_block.0.borrow_mut();
+                let var = parent_bare.vars.get_mut(map_name)?;
+                if let Value::Map(ref mut entries) = var.value {
+                    let pos = entries.iter().position(|(entry_key, _)| *entry_key == key);
+                    if name == "remove" {
+                        return pos.map(|pos| VarVal::from_string(entries.remove(pos).1));
+                    }
+                    let res = pos.map(|pos| VarVal::from_string(&entries[pos].1));
+                    if let Some(val) = val {
+                        // set
+                        match pos {
+                            Some(pos) => entries[pos].1 = val,
+                            None => entries.push((key, val)),
+                        }
+                    }
+                    return res; // get/set
+                } else {
+                    log.error(&format!{"Specified argument {} isn't a map at {}:{}: ",  map_name, fun_block.script_path(), fun_block.script_line});
+                }
+            }
+            "keys" | "values" => {
+                if fun_block.params.len() != 1 {
+                    log.error(&format!{"The '{name}' requires 1 parameter, but specified {} at {}:{}: ", fun_block.params.len(), fun_block.script_path(), fun_block.script_line});
+                    return None;
+                }
+                match self.prev_or_search_up(&fun_block.params[0], res_prev) {
+                    Some(VarVal { value: Value::Map(entries), .. }) => {
+                        let (keys, values): (Vec<_>, Vec<_>) = entries.into_iter().unzip();
+                        return Some(VarVal::from_vec(if name == "keys" { keys } else { values }));
+                    }
+                    _ => log.error(&format!{"Specified argument {} isn't a map at {}:{}: ",  fun_block.params[0], fun_block.script_path(), fun_block.script_line}),
+                }
+            }
             "set_env" => {
                 if fun_block.params.len() != 2 {
                     log.error(&format!{"Set environment requires 2 parameters, but specified {} at {}:{}: ", fun_block.params.len(), fun_block.script_path(), fun_block.script_line})
@@ -1609,7 +2078,7 @@ impl GenBlockTup {
                     if !has_root(&file)
                         && let Some(ref path) = cwd
                     {
-                        file = path.value.clone() + MAIN_SEPARATOR_STR + &file
+                        file = path.text() + MAIN_SEPARATOR_STR + &file
                     }
                     // str ends / then search will be in the current dir and all subdirs
                     let recursive = file.ends_with(MAIN_SEPARATOR);
@@ -1630,21 +2099,21 @@ impl GenBlockTup {
                 let start: usize = start.parse().ok()?;
                 let str_val;
                 if let Some(var) = fun_block.prev_or_search_up(&fun_block.params[0], res_prev) {
-                    if var.val_type == VarType::Array {
+                    if let Value::Array(ref values) = var.value {
                         let end = if fun_block.params.len() > 2 {
                             (*self.parameter(log, 2, fun_block, res_prev))
                                 .parse()
                                 .ok()?
                         } else {
-                            var.values.len()
+                            values.len()
                         };
                         if start < end {
-                            return Some(VarVal::from_vec(var.values[start..end].to_vec()));
+                            return Some(VarVal::from_vec(values[start..end].to_vec()));
                         } else {
                             return None;
                         }
                     }
-                    str_val = var.value
+                    str_val = var.into_text()
                 } else {
                     str_val = fun_block.params[0].clone()
                 }
@@ -1664,7 +2133,7 @@ impl GenBlockTup {
                 let len = fun_block.params.len();
                 let cwd = fun_block
                     .search_up(CWD)
-                    .map(|cwd| cwd.value)
+                    .map(|cwd| cwd.into_text())
                     .unwrap_or_default();
                 for mut i in (0..len).step_by(2) {
                     let mut file_from = *self.parameter(log, i, fun_block, res_prev);
@@ -1681,8 +2150,13 @@ impl GenBlockTup {
                             file_to += &(MAIN_SEPARATOR_STR.to_owned()
                                 + PathBuf::from(&file_from).file_name()?.to_str()?)
                         }
-                        if copy(&file_from, &file_to).is_ok() {
-                            res.push(file_to) // possibly size copied
+                        match copy(&file_from, &file_to) {
+                            Ok(_) => res.push(file_to), // possibly size copied
+                            Err(err) => {
+                                let msg = format! {"Copying {file_from} to {file_to} failed at {}:{}: , reason {err}", fun_block.script_path(), fun_block.script_line};
+                                log.error(&msg);
+                                report_failure(msg)
+                            }
                         }
                     }
                 }
@@ -1692,7 +2166,7 @@ impl GenBlockTup {
                 let mut res: Vec<_> = Vec::new();
                 let cwd = fun_block
                     .search_up(CWD)
-                    .map(|cwd| cwd.value)
+                    .map(|cwd| cwd.into_text())
                     .unwrap_or_default();
                 for mut i in (0..fun_block.params.len()).step_by(2) {
                     let mut file_from = *self.parameter(log, i, fun_block, res_prev);
@@ -1721,7 +2195,7 @@ impl GenBlockTup {
                 let mut res: Vec<_> = Vec::new();
                 let cwd = fun_block
                     .search_up(CWD)
-                    .map(|cwd| cwd.value)
+                    .map(|cwd| cwd.into_text())
                     .unwrap_or_default();
                 for i in 0..fun_block.params.len() {
                     let mut file = *self.parameter(log, i, fun_block, res_prev);
@@ -1729,8 +2203,13 @@ impl GenBlockTup {
                         if !has_root(&file) {
                             file = cwd.clone() + MAIN_SEPARATOR_STR + &file
                         }
-                        if create_dir_all(&file).is_ok() {
-                            res.push(file)
+                        match create_dir_all(&file) {
+                            Ok(()) => res.push(file),
+                            Err(err) => {
+                                let msg = format! {"Directory {file} can't be created at {}:{}: , reason {err}", fun_block.script_path(), fun_block.script_line};
+                                log.error(&msg);
+                                report_failure(msg)
+                            }
                         }
                     }
                 }
@@ -1740,7 +2219,7 @@ impl GenBlockTup {
                 let mut res: Vec<_> = Vec::new();
                 let cwd = fun_block
                     .search_up(CWD)
-                    .map(|cwd| cwd.value)
+                    .map(|cwd| cwd.into_text())
                     .unwrap_or_default();
                 for i in 0..fun_block.params.len() {
                     let mut file = *self.parameter(log, i, fun_block, res_prev);
@@ -1759,7 +2238,7 @@ impl GenBlockTup {
                 let mut res: Vec<_> = Vec::new();
                 let cwd = fun_block
                     .search_up(CWD)
-                    .map(|cwd| cwd.value)
+                    .map(|cwd| cwd.into_text())
                     .unwrap_or_default();
                 for i in 0..fun_block.params.len() {
                     let mut file = *self.parameter(log, i, fun_block, res_prev);
@@ -1800,12 +2279,14 @@ impl GenBlockTup {
             }
             "number" => {
                 let val = *self.parameter(log, 0, fun_block, res_prev);
-                let num = if val.is_empty() {
-                    0
-                } else {
-                    val.parse::<i32>().ok()?
+                if val.is_empty() {
+                    return Some(VarVal::from_i64(0));
+                }
+                return match Value::Str(val).typed() {
+                    Value::Int(num) => Some(VarVal::from_i64(num)),
+                    Value::Float(num) => Some(VarVal::from_f64(num)),
+                    _ => None,
                 };
-                return Some(VarVal::from_i32(num));
             }
             "zip" => {
                 // variable parameters
@@ -1820,7 +2301,7 @@ impl GenBlockTup {
                 if !has_root(&zip_path)
                     && let Some(ref cwd) = cwd
                 {
-                    zip_path = cwd.value.clone() + MAIN_SEPARATOR_STR + &zip_path
+                    zip_path = cwd.text() + MAIN_SEPARATOR_STR + &zip_path
                 }
                 if zip_path.find('.').is_none() {
                     zip_path += ".zip"
@@ -1874,7 +2355,7 @@ impl GenBlockTup {
                         if !has_root(&files)
                             && let Some(ref cwd) = cwd
                         {
-                            files = cwd.value.clone() + MAIN_SEPARATOR_STR + &files
+                            files = cwd.text() + MAIN_SEPARATOR_STR + &files
                         }
                         let files = Path::new(&files);
                         assert!(&files.has_root());
@@ -1931,7 +2412,7 @@ impl GenBlockTup {
                                 &comment.map(String::from),
                             )
                         } else {
-                            log.error(&format!{"Path {files:?} can't be zipped at {}:{}: ", fun_block.script_path(), fun_block.script_line})
+                            function_error(log, format!{"Path {files:?} can't be zipped at {}:{}: ", fun_block.script_path(), fun_block.script_line})
                         }
                     } else if op.starts_with("-B") {
                         // probably -C takes all cases
@@ -1945,12 +2426,12 @@ impl GenBlockTup {
                         //println!{"found pd {zipped_path:?} for {}", &flatten_params[current_op]}
                         // nested to nested case?
                         let values = if let Some(zipped_path) = zipped_path {
-                            if zipped_path.val_type == VarType::Array {
-                                //println!{"it's array of {}", zipped_path.values.len()}
-                                zipped_path.values
+                            if zipped_path.is_array() {
+                                //println!{"it's array of {}", zipped_path.values().len()}
+                                zipped_path.into_values()
                             } else {
                                 //println!{"not array, {}", zipped_path.value}
-                                vec![zipped_path.value]
+                                vec![zipped_path.into_text()]
                             }
                         } else {
                             let mut values = vec![];
@@ -1973,7 +2454,7 @@ impl GenBlockTup {
                             if !has_root(&entry)
                                 && let Some(ref cwd) = cwd
                             {
-                                entry = cwd.value.clone() + MAIN_SEPARATOR_STR + &entry
+                                entry = cwd.text() + MAIN_SEPARATOR_STR + &entry
                             }
                             let entry_path = Path::new(&entry);
                             let parent_files = entry_path.parent().unwrap_or(Path::new("."));
@@ -2061,7 +2542,11 @@ impl GenBlockTup {
                 }
                 match zip.store() {
                     Ok(()) => return Some(VarVal::from_string(zip_path)),
-                    Err(msg) => log.error(&format!{"Zip: {msg} at {}:{}: ", fun_block.script_path(), fun_block.script_line})
+                    Err(msg) => {
+                        let msg = format! {"Zip: {msg} at {}:{}: ", fun_block.script_path(), fun_block.script_line};
+                        log.error(&msg);
+                        report_failure(msg)
+                    }
                 }
             }
             "cfg" => {
@@ -2086,21 +2571,214 @@ impl GenBlockTup {
                 }
                 return Some(VarVal::from_string(cfg_path));
             }
-            _ => todo!(
-                "no such function: {} at {}:{}: ",
-                fun_block.name.clone().unwrap_or_default().red(),
-                fun_block.script_path(),
-                &fun_block.script_line
-            ),
+            _ => match self.search_fun(name) {
+                Some(fun_def) => return self.exec_user_fun(log, &fun_def, fun_block, res_prev),
+                None => report_script_error(
+                    fun_block.script_error(format!("No such function {}", name.bold())),
+                ),
+            },
         }
         None
     }
 
-    pub fn exec_assign(
-        &self,
-        log: &Log,
-        fun_block: &GenBlock,
-        res_prev: &Option<VarVal>,
+    /// executes a 'try' body, a failure in it is passed to 'catch', and 'finally' is always executed
+    pub fn exec_try(&self, log: &Log, prev_res: &Option<VarVal>) -> Option<VarVal> {
+        let children = self.borrow().children.clone();
+        let (handlers, body): (Vec<_>, Vec<_>) = children.into_iter().partition(|child| {
+            matches!(child.borrow().block_type, BlockType::Catch | BlockType::Finally)
+        });
+        let mut res = prev_res.clone();
+        TRY_DEPTH.fetch_add(1, Ordering::Relaxed);
+        for child in body {
+            res = child.exec(log, &res);
+            if interrupted() {
+                break;
+            }
+        }
+        TRY_DEPTH.fetch_sub(1, Ordering::Relaxed);
+        for handler in handlers {
+            let block_type = handler.borrow().block_type.clone();
+            match block_type {
+                // errors of the script aren't recoverable
+                BlockType::Catch if failure_pending() && SCRIPT_ERROR.read().unwrap().is_none() => {
+                    let (function, location) = FAILURE_ORIGIN.write().unwrap().take().unwrap_or_default();
+                    let reason = take_failure().unwrap_or_default();
+                    log.debug(&format!("caught {reason} of {function} at {location}"));
+                    let name = handler.borrow().name.clone();
+                    if let Some(name) = name {
+                        let mut catch_block = handler.borrow_mut();
+                        catch_block.vars.insert(format!("{name}.function"), VarVal::from_string(function));
+                        catch_block.vars.insert(format!("{name}.location"), VarVal::from_string(location));
+                        catch_block.vars.insert(name, VarVal::from_string(reason.trim_end().trim_end_matches(':')));
+                    }
+                    res = handler.exec(log, &res)
+                }
+                BlockType::Finally => {
+                    let origin = FAILURE_ORIGIN.write().unwrap().take();
+                    let failure = take_failure();
+                    let jump = take_jump();
+                    res = handler.exec(log, &res);
+                    // a failure of 'finally' replaces the pending one
+                    if !interrupted() {
+                        restore_failure(failure);
+                        *FAILURE_ORIGIN.write().unwrap() = origin;
+                        *JUMP.write().unwrap() = jump
+                    }
+                }
+                _ => (),
+            }
+        }
+        res
+    }
+
+    /// values of a 'for' defined by a function, as range(from,to[,step]), lines(file), or
+    /// any function returning an array
+    fn function_range(
+        &self,
+        log: &Log,
+        for_block: &GenBlock,
+        fun_name: &str,
+        prev_res: &Option<VarVal>,
+    ) -> Option<Vec<String>> {
+        let params: Vec<String> = (0..for_block.params.len())
+            .map(|i| *self.parameter(log, i, for_block, prev_res))
+            .collect();
+        match fun_name {
+            "range" => {
+                let numbers: Result<Vec<i64>, _> =
+                    params.iter().map(|param| param.trim().parse::<i64>()).collect();
+                let (from, to, step) = match numbers.as_deref() {
+                    Ok([from, to]) => (*from, *to, 1),
+                    Ok([from, to, step]) if *step != 0 => (*from, *to, *step),
+                    _ => {
+                        log.error(&format!(
+                            "A 'for' range requires 2 or 3 numbers with a not zero step, but specified {:?} at {}:{}: ",
+                            params,
+                            for_block.script_path(),
+                            for_block.script_line
+                        ));
+                        return None;
+                    }
+                };
+                let mut range = Vec::new();
+                let mut current = from;
+                while step > 0 && current < to || step < 0 && current > to {
+                    range.push(current.to_string());
+                    current += step
+                }
+                Some(range)
+            }
+            "lines" => {
+                let mut fname = params.first()?.to_string();
+                if !has_root(&fname)
+                    && let Some(cwd) = for_block.search_up(CWD)
+                {
+                    fname = cwd.into_text() + MAIN_SEPARATOR_STR + &fname
+                }
+                match fs::read_to_string(&fname) {
+                    Ok(content) => Some(content.lines().map(String::from).collect()),
+                    Err(err) => {
+                        function_error(log, format!{"File {} can't be read, {} at {}:{}: ", fname, err, for_block.script_path(), for_block.script_line});
+                        None
+                    }
+                }
+            }
+            _ => {
+                let mut fun_block = GenBlock::new(BlockType::Function);
+                fun_block.name = Some(fun_name.to_string());
+                fun_block.params = for_block.params.clone();
+                fun_block.parent = Some(self.clone());
+                fun_block.script_line = for_block.script_line;
+                let res = self.exec_fun(log, &fun_block, prev_res)?;
+                if res.is_array() {
+                    Some(res.into_values())
+                } else {
+                    let value = res.into_text();
+                    if value.is_empty() {
+                        Some(Vec::new())
+                    } else {
+                        Some(vec![value])
+                    }
+                }
+            }
+        }
+    }
+
+    /// finds a user defined function visible from the block
+    pub fn search_fun(&self, name: &str) -> Option<GenBlockTup> {
+        let mut current_bl = self.clone();
+        loop {
+            let current_bare = current_bl.borrow();
+            for ch in &current_bare.children {
+                let ch_block = ch.borrow();
+                if ch_block.block_type == BlockType::Fun && ch_block.name.as_deref() == Some(name) {
+                    return Some(ch.clone());
+                }
+            }
+            let parent = current_bare.parent.clone()?;
+            drop(current_bare);
+            current_bl = parent
+        }
+    }
+
+    /// executes the body of a user defined function with parameters bound as its variables,
+    /// the result of the last statement is returned
+    pub fn exec_user_fun(
+        &self,
+        log: &Log,
+        fun_def: &GenBlockTup,
+        fun_block: &GenBlock,
+        res_prev: &Option<VarVal>,
+    ) -> Option<VarVal> {
+        let (name, params) = {
+            let def_block = fun_def.borrow();
+            (def_block.name.clone().unwrap_or_default(), def_block.params.clone())
+        };
+        if params.len() != fun_block.params.len() {
+            report_script_error(fun_block.script_error(format!(
+                "Function '{name}' requires {} parameters, but specified {}",
+                params.len(),
+                fun_block.params.len()
+            )));
+            return None;
+        }
+        let mut args = Vec::with_capacity(params.len());
+        for (i, param) in fun_block.params.iter().enumerate() {
+            let arg = match fun_block.prev_or_search_up(param, res_prev) {
+                Some(var) if var.is_array() => var,
+                _ => VarVal::from_string(*self.parameter(log, i, fun_block, res_prev)),
+            };
+            args.push(arg)
+        }
+        // variables of the definition are restored after the call, so calls can be nested
+        let saved_vars = fun_def.borrow().vars.clone();
+        {
+            let mut def_block = fun_def.borrow_mut();
+            for (param, arg) in params.into_iter().zip(args) {
+                def_block.vars.insert(param, arg);
+            }
+        }
+        let children = fun_def.borrow().children.clone();
+        let mut res = res_prev.clone();
+        for child in children {
+            res = child.exec(log, &res);
+            if interrupted() {
+                break;
+            }
+        }
+        fun_def.borrow_mut().vars = saved_vars;
+        // stop() keeps unwinding up to the target
+        if *JUMP.read().unwrap() == Some(Jump::Return) {
+            take_jump();
+        }
+        res
+    }
+
+    pub fn exec_assign(
+        &self,
+        log: &Log,
+        fun_block: &GenBlock,
+        res_prev: &Option<VarVal>,
     ) -> Option<VarVal> {
         let name = fun_block.params[0].to_owned();
         let mut parent = self.parent()?; // because fun block can't be Main
@@ -2112,6 +2790,7 @@ impl GenBlockTup {
             if close_scope.is_none()
                 && (parent_bare.block_type == BlockType::Scope
                     || parent_bare.block_type == BlockType::Target
+                    || parent_bare.block_type == BlockType::Fun
                     || parent_bare.block_type == BlockType::Main)
             {
                 close_scope = Some(parent.clone())
@@ -2199,29 +2878,8 @@ impl GenBlockTup {
                     process_template_value(log, param_val, fun_block, res_prev)
                 }
             }
-            // TODO extend  val.value accordingly val.val_type
             Some(val) => {
-                let var = match val.val_type {
-                    VarType::Environment => match env::var(&val.value) {
-                        Ok(val) => val,
-                        Err(_e) => val.value,
-                    },
-                    VarType::Property => {
-                        if let Some(val) = get_property(&val.value) {
-                            val
-                        } else {
-                            val.value
-                        }
-                    }
-                    //VarType::Array => util:: vec_to_str( &val.values),
-                    _ => {
-                        if val.values.is_empty() {
-                            val.value
-                        } else {
-                            util::vec_to_str(&val.values)
-                        }
-                    }
-                };
+                let var = val.resolved().to_string();
                 process_template_value(log, &var, fun_block, res_prev)
             }
         }
@@ -2234,12 +2892,12 @@ impl GenBlockTup {
         res_prev: &Option<VarVal>,
     ) -> Option<String> {
         let Some(vec_param) = val else { return None };
-        if vec_param.val_type == VarType::Array {
+        if vec_param.is_array() {
             Some(
                 vec_param
-                    .values
-                    .clone()
-                    .into_iter()
+                    .values()
+                    .iter()
+                    .cloned()
                     .map(|v| {
                         if let Some(v) = self.prev_or_search_up(&v, res_prev) {
                             self.array_to_string(&Some(v), sep, res_prev)
@@ -2252,7 +2910,7 @@ impl GenBlockTup {
                     .join(sep),
             )
         } else {
-            Some(vec_param.value.clone())
+            Some(vec_param.text())
         }
     }
 
@@ -2304,7 +2962,7 @@ impl GenBlockTup {
                             state = CalcState::Oper;
                             let var: String = buf_var[0..name_pos].iter().collect();
                             if let Some(val) = self.search_up(&var) {
-                                if let Ok(val) = val.value.parse::<f64>() {
+                                if let Some(val) = val.resolved().as_f64() {
                                     match op {
                                         Op::Div => {
                                             if val == 0.0 {
@@ -2325,7 +2983,7 @@ impl GenBlockTup {
                             op = match c {
                                 '*' => Op::Mul,
                                 '/' => Op::Div,
-                                _ => todo!(),
+                                _ => return Err((CalcErrCause::InvOp, pos)),
                             };
                         }
                         CalcState::Val => {
@@ -2350,7 +3008,7 @@ impl GenBlockTup {
                             op = match c {
                                 '*' => Op::Mul,
                                 '/' => Op::Div,
-                                _ => todo!(),
+                                _ => return Err((CalcErrCause::InvOp, pos)),
                             };
                         }
                         CalcState::Exp => {
@@ -2369,10 +3027,10 @@ impl GenBlockTup {
                             op = match c {
                                 '*' => Op::Mul,
                                 '/' => Op::Div,
-                                _ => todo!(),
+                                _ => return Err((CalcErrCause::InvOp, pos)),
                             };
                         }
-                        _ => todo!("state: {:?}", state),
+                        _ => return Err((CalcErrCause::InvOp, pos)),
                     }
                 }
                 '+' | '-' => {
@@ -2391,7 +3049,7 @@ impl GenBlockTup {
                             let var: String = buf_var[0..name_pos].iter().collect();
                             if let Some(val) = self.search_up(&var) {
                                 //println!{"st {state:?} car {c} val {}", val.value};
-                                if let Ok(val) = val.value.parse::<f64>() {
+                                if let Some(val) = val.resolved().as_f64() {
                                     match op {
                                         Op::Div => {
                                             if val == 0.0 {
@@ -2496,7 +3154,7 @@ impl GenBlockTup {
                                 _ => None,
                             };
                         }
-                        _ => todo!("state: {:?}", state),
+                        _ => return Err((CalcErrCause::InvOp, pos)),
                     }
                 }
                 '0'..='9' | '.' => {
@@ -2513,7 +3171,7 @@ impl GenBlockTup {
                             buf_var[name_pos] = c;
                             name_pos += 1;
                         }
-                        _ => todo!("state: {:?}", state),
+                        _ => return Err((CalcErrCause::InvOp, pos)),
                     }
                 }
                 '(' => {
@@ -2540,7 +3198,7 @@ impl GenBlockTup {
                         CalcState::Var => {
                             let var: String = buf_var[0..name_pos].iter().collect();
                             if let Some(val) = self.search_up(&var) {
-                                if let Ok(val) = val.value.parse::<f64>() {
+                                if let Some(val) = val.resolved().as_f64() {
                                     match op {
                                         Op::Div => {
                                             if val == 0.0 {
@@ -2627,7 +3285,7 @@ impl GenBlockTup {
                             name_pos += 1;
                         }
 
-                        _ => todo!("state: {:?}", state),
+                        _ => return Err((CalcErrCause::InvOp, pos)),
                     }
                 }
             }
@@ -2642,7 +3300,7 @@ impl GenBlockTup {
                 let var: String = buf_var[0..name_pos].iter().collect();
                 //println!{"st {state:?} var {var}"};
                 if let Some(val) = self.search_up(&var) {
-                    if let Ok(val) = val.value.parse::<f64>() {
+                    if let Some(val) = val.resolved().as_f64() {
                         exp_val = val
                     } else {
                         return Err((CalcErrCause::NaN, pos));
@@ -2660,7 +3318,7 @@ impl GenBlockTup {
                 }
             }
             CalcState::Exp => (),
-            _ => todo!("state: {:?}", state),
+            _ => return Err((CalcErrCause::InvOp, pos)),
         }
         match state {
             CalcState::Exp | CalcState::Val | CalcState::Var => {
@@ -2693,26 +3351,19 @@ impl GenBlockTup {
 
 pub fn run(log: &Log, block: GenBlockTup, targets: &mut Vec<String>) -> Result<(), Box<dyn Error>> {
     block.exec(log, &None); // execute Main
+    if let Some(err) = take_script_error() {
+        return Err(Box::new(err));
+    }
+    if let Some(reason) = take_failure() {
+        return Err(Box::new(BuildFailure(reason)));
+    }
     if targets.is_empty() {
-        let mut tar_name: Option<String> = None;
-        for ch in &block.borrow().children.clone() {
-            let ch_block = ch.0.borrow();
-            if ch_block.block_type == BlockType::Target {
-                tar_name = Some(
-                    ch_block
-                        .name
-                        .as_ref()
-                        .ok_or("no 'target' block name")?
-                        .to_string(),
-                )
-            }
-        }
-        let Some(tar_name) = tar_name else {
-            return Err(Box::new("No targets found in the script".red()));
-        };
-        targets.push(tar_name)
+        targets.push(default_target(&block)?)
     }
     log.log(&format!("targets: {:?}", targets));
+    check_cycles(&block, targets)?;
+    let keep_going = block.search_up("~keep-going~").is_some();
+    let mut failed = false;
     'targets: for target in targets {
         log.log(&format!(
             "processing for '{}' of {}",
@@ -2727,23 +3378,71 @@ pub fn run(log: &Log, block: GenBlockTup, targets: &mut Vec<String>) -> Result<(
                 && ch_block.name.as_ref().ok_or("no 'target' block name")? == target
             {
                 drop(ch_block);
-                log.log(&format!(
-                    "target: {}",
-                    exec_target(log, &bl, bl.search_up("~build-given-target~").is_some())
-                ));
+                let res = exec_target(log, &bl, bl.search_up("~build-given-target~").is_some());
+                if let Some(err) = take_script_error() {
+                    return Err(Box::new(err));
+                }
+                match res {
+                    Ok(executed) => log.log(&format!("target: {}", executed)),
+                    Err(failure) => {
+                        if failure.target == *target {
+                            log.error(&format!("Target '{}' failed: {}", target, failure.reason));
+                        } else {
+                            log.error(&format!(
+                                "Target '{}' skipped, because the target '{}' failed",
+                                target, failure.target
+                            ));
+                        }
+                        if !keep_going {
+                            return Err(Box::new(BuildFailure(format!(
+                                "Target '{}' failed",
+                                failure.target.bold()
+                            ))));
+                        }
+                        failed = true
+                    }
+                }
                 continue 'targets;
             }
         }
         let target = target.clone().bold();
         return Err(format!("No target '{target}' found").red().into());
     }
+    if !failed {
+        return Ok(());
+    }
+    // dependencies of the requested targets are in the summary too
+    build_summary(log, &target_failures())
+}
 
-    Ok(())
+/// executes a target once per invocation, later calls reuse its result
+pub fn exec_target(
+    log: &Log,
+    target_bl: &GenBlockTup,
+    force_exec: bool,
+) -> Result<bool, TargetFailure> {
+    let name = target_bl.borrow().name.clone().unwrap_or_default();
+    if let Some(res) = get_target_result(&name) {
+        log.debug(&format!("target {name} was already processed: {res:?}"));
+        log.explain(&format!("target '{name}' was already processed, its result is reused"));
+        return res;
+    }
+    let res = eval_target(log, target_bl, force_exec);
+    set_target_result(&name, res.clone());
+    res
 }
 
-pub fn exec_target(log: &Log, target_bl: &GenBlockTup, force_exec: bool) -> bool {
+fn eval_target(
+    log: &Log,
+    target_bl: &GenBlockTup,
+    force_exec: bool,
+) -> Result<bool, TargetFailure> {
     // dependencies
     let mut need_exec = false;
+    // keep a failure of a dependent target, if any
+    let pending = take_failure();
+    // 'changed' evaluated by dependent targets are already taken
+    let changed_from = CHANGED_STATES.read().unwrap().len();
 
     let gl_cwd = target_bl.search_up(CWD);
     let mut target = target_bl.borrow_mut();
@@ -2760,13 +3459,13 @@ pub fn exec_target(log: &Log, target_bl: &GenBlockTup, force_exec: bool) -> bool
             //let mut dir = *process_template_value(log, &dir_val, &target, &None);
             let mut dir = match target.search_up(&dir_val) {
                 None => dir_val,
-                Some(var) => var.value,
+                Some(var) => var.into_text(),
             };
             // calculate it upon current cwd
             if !has_root(&dir)
                 && let Some(cwd) = gl_cwd
             {
-                dir = cwd.value + std::path::MAIN_SEPARATOR_STR + &dir
+                dir = cwd.into_text() + std::path::MAIN_SEPARATOR_STR + &dir
             }
             let path = Path::new(&dir);
             if path.exists() {
@@ -2781,21 +3480,652 @@ pub fn exec_target(log: &Log, target_bl: &GenBlockTup, force_exec: bool) -> bool
     }
     drop(target);
     let target = target_bl.borrow();
+    let name = target.name.clone().unwrap_or_default();
+    // all dependencies are evaluated when the build keeps going, so independent targets are executed
+    let keep_going = target.search_up("~keep-going~").is_some();
+    let mut failures = Vec::new();
     for dep in &target.deps {
-        need_exec |= dep.eval_dep(log, &None)
+        match dep.eval_dep(log, &None) {
+            Ok(dep_res) => need_exec |= dep_res,
+            Err(failure) => {
+                failures.push(failure);
+                if !keep_going {
+                    break;
+                }
+            }
+        }
     }
-    need_exec = need_exec || target.search_up("~force-build-target~").is_some() || force_exec;
+    if let Some(failure) = combined_failure(failures) {
+        record_changed(log, changed_from, false);
+        restore_failure(pending);
+        return Err(failure);
+    }
+    let forced_all = target.search_up("~force-build-target~").is_some();
     if need_exec {
+        log.explain(&format!("target '{name}' is executed, because a dependency is true"))
+    } else if forced_all {
+        log.explain(&format!("target '{name}' is executed, because all targets are forced by -r"))
+    } else if force_exec {
+        log.explain(&format!("target '{name}' is executed, because the requested target is forced by -c"))
+    } else if target.deps.is_empty() {
+        log.explain(&format!("target '{name}' isn't executed, because it has no dependencies"))
+    } else {
+        log.explain(&format!("target '{name}' is up to date, because no dependency is true"))
+    }
+    need_exec = need_exec || forced_all || force_exec;
+    // a script error can be found evaluating dependencies
+    if need_exec && !failure_pending() {
         let mut res = None;
         let children = target.children.clone();
         drop(target);
         for child in children {
-            res = child.exec(log, &res)
+            res = child.exec(log, &res);
+            if interrupted() {
+                break;
+            }
         }
+        take_jump();
     } else {
         log.debug(&format!("no need to run: {:?}", target_bl.borrow().name))
     }
-    need_exec
+    let store = need_exec && !failure_pending() && target_bl.search_up("~dry-run~").is_none();
+    record_changed(log, changed_from, store);
+    let failure = take_failure();
+    restore_failure(pending);
+    match failure {
+        Some(reason) => Err(TargetFailure {
+            target: name,
+            reason,
+        }),
+        _ => Ok(need_exec),
+    }
+}
+
+/// executes targets by parallel jobs, every job is a separate RustBee process running
+/// one target, a target gets launched when all its target(...) dependencies are complete
+pub fn run_jobs(
+    log: &Log,
+    block: GenBlockTup,
+    targets: &mut Vec<String>,
+    jobs: usize,
+    job_args: &[String],
+) -> Result<(), Box<dyn Error>> {
+    if targets.is_empty() {
+        targets.push(default_target(&block)?)
+    }
+    log.log(&format!("targets: {:?} by {} jobs", targets, jobs));
+    // graph of target dependencies in the order of discovering
+    let mut graph: Vec<(String, Vec<String>)> = Vec::new();
+    let mut queue: Vec<String> = targets.clone();
+    while let Some(name) = queue.pop() {
+        if graph.iter().any(|(target, _)| *target == name) {
+            continue;
+        }
+        let Some(target) = block.get_target(&name) else {
+            let name = name.bold();
+            return Err(format!("No target '{name}' found").red().into());
+        };
+        let deps: Vec<String> = target_deps(&target).into_iter().map(|dep| dep.name).collect();
+        queue.extend(deps.iter().cloned());
+        graph.push((name, deps))
+    }
+    check_cycles(&block, targets)?;
+    let exe = env::current_exe()?;
+    let script = block.search_up(crate::SCRIPT).ok_or("no script path")?.into_text();
+    let script = fs::canonicalize(&script).unwrap_or(PathBuf::from(script));
+    let cwd = block.search_up(CWD);
+    let force_given = block.search_up("~build-given-target~").is_some();
+    let keep_going = block.search_up("~keep-going~").is_some();
+
+    let mut results: HashMap<String, Result<bool, TargetFailure>> = HashMap::new();
+    let mut launched: Vec<String> = Vec::new();
+    let mut running = 0;
+    let mut stop = false;
+    let (sender, receiver) = mpsc::channel();
+    loop {
+        // a target with a failed dependency is skipped, and it can make skipped others
+        let mut skipped = true;
+        while skipped {
+            skipped = false;
+            for (name, deps) in &graph {
+                if results.contains_key(name) {
+                    continue;
+                }
+                // a target is skipped when all its dependencies are complete, so every failed one is known
+                if !deps.iter().all(|dep| results.contains_key(dep)) {
+                    continue;
+                }
+                let failures = deps.iter().filter_map(|dep| results.get(dep)?.clone().err()).collect();
+                if let Some(failure) = combined_failure(failures) {
+                    results.insert(name.to_string(), Err(failure));
+                    skipped = true
+                }
+            }
+        }
+        for (name, deps) in &graph {
+            if stop || running >= jobs {
+                break;
+            }
+            if launched.contains(name)
+                || results.contains_key(name)
+                || !deps.iter().all(|dep| results.contains_key(dep))
+            {
+                continue;
+            }
+            let mut command = Command::new(&exe);
+            command.arg("-f").arg(&script).arg("--job").arg(name);
+            for dep in deps {
+                let state = match results.get(dep) {
+                    Some(Ok(true)) => "executed",
+                    _ => "uptodate",
+                };
+                command.arg("--target-done").arg(format!("{dep}:{state}"));
+            }
+            if force_given && targets.contains(name) {
+                command.arg("-c");
+            }
+            command.args(job_args).stdin(Stdio::null());
+            if let Some(cwd) = &cwd {
+                command.current_dir(cwd.text());
+            }
+            log.debug(&format!("launching job: {command:?}"));
+            let sender = sender.clone();
+            let job = name.to_string();
+            std::thread::spawn(move || {
+                let _ = sender.send((job, command.output()));
+            });
+            launched.push(name.to_string());
+            running += 1
+        }
+        if running == 0 {
+            break;
+        }
+        let (name, output) = receiver.recv()?;
+        running -= 1;
+        let res = match output {
+            Ok(output) => {
+                log.message(&format!("{} {}", "Target".bold(), name.bold()));
+                for line in String::from_utf8_lossy(&output.stdout).lines() {
+                    println!("{line}");
+                    log.tee(line)
+                }
+                for line in String::from_utf8_lossy(&output.stderr).lines() {
+                    eprintln!("{line}");
+                    log.tee(line)
+                }
+                let reason = || job_failure(&output.stderr, &name);
+                match output.status.code() {
+                    Some(0) => Ok(true),
+                    Some(JOB_UP_TO_DATE_EXIT_CODE) => Ok(false),
+                    Some(code) => Err(reason().unwrap_or_else(|| format!("the job exited with code {code}"))),
+                    _ => Err(reason().unwrap_or_else(|| "the job was terminated by a signal".to_string())),
+                }
+            }
+            Err(err) => Err(format!("the job couldn't be launched: {err}")),
+        };
+        let res = res.map_err(|reason| TargetFailure {
+            target: name.to_string(),
+            reason,
+        });
+        stop |= res.is_err() && !keep_going;
+        results.insert(name, res);
+    }
+    if !stop && results.len() < graph.len() {
+        let pending: Vec<_> = graph
+            .iter()
+            .filter(|(name, _)| !results.contains_key(name))
+            .map(|(name, _)| name)
+            .collect();
+        return Err(format!("Targets {pending:?} can't be scheduled, check their dependencies").red().into());
+    }
+    let mut failed = false;
+    for target in targets.iter() {
+        let Some(Err(failure)) = results.get(target) else {
+            continue;
+        };
+        if failure.target == *target {
+            log.error(&format!("Target '{}' failed: {}", target, failure.reason));
+        } else {
+            log.error(&format!(
+                "Target '{}' skipped, because the target '{}' failed",
+                target, failure.target
+            ));
+        }
+        if !keep_going {
+            return Err(Box::new(BuildFailure(format!(
+                "Target '{}' failed",
+                failure.target.bold()
+            ))));
+        }
+        failed = true
+    }
+    if stop && !failed {
+        // a dependency failed, so the requested targets weren't reached
+        if let Some(Err(failure)) = results.values().find(|res| res.is_err()) {
+            return Err(Box::new(BuildFailure(format!(
+                "Target '{}' failed",
+                failure.target.bold()
+            ))));
+        }
+    }
+    if !failed {
+        return Ok(());
+    }
+    // dependencies are discovered after targets depending on them
+    let failures: Vec<(String, TargetFailure)> = graph
+        .iter()
+        .rev()
+        .filter_map(|(name, _)| Some((name.to_string(), results.get(name)?.clone().err()?)))
+        .collect();
+    build_summary(log, &failures)
+}
+
+/// a reason of a failed job as the job reported it, or its last error
+fn job_failure(stderr: &[u8], target: &str) -> Option<String> {
+    let stderr = util::strip_colors(&String::from_utf8_lossy(stderr));
+    let failed = format!("Error: Target '{target}' failed: ");
+    stderr
+        .lines()
+        .rev()
+        .find_map(|line| line.strip_prefix(&failed))
+        .or_else(|| stderr.lines().rev().find_map(|line| line.strip_prefix("Error: ")))
+        .map(|reason| reason.trim().to_string())
+}
+
+/// the last target of the script
+fn default_target(block: &GenBlockTup) -> Result<String, Box<dyn Error>> {
+    let mut tar_name: Option<String> = None;
+    for ch in &block.borrow().children.clone() {
+        let ch_block = ch.0.borrow();
+        if ch_block.block_type == BlockType::Target {
+            tar_name = Some(
+                ch_block
+                    .name
+                    .as_ref()
+                    .ok_or("no 'target' block name")?
+                    .to_string(),
+            )
+        }
+    }
+    let Some(tar_name) = tar_name else {
+        return Err(Box::new("No targets found in the script".red()));
+    };
+    Ok(tar_name)
+}
+
+/// names of targets given in dependencies as target(...)
+fn target_deps(target: &GenBlockTup) -> Vec<TargetDep> {
+    let mut res = Vec::new();
+    for dep in &target.borrow().deps {
+        let dep = dep.borrow();
+        if dep.children.len() != 1 {
+            continue;
+        }
+        let dep_block = dep.children[0].borrow();
+        if dep_block.block_type == BlockType::Function
+            && dep_block.name.as_deref() == Some("target")
+            && let Some(name) = dep_block.params.first()
+            && target.get_target(name).is_some()
+        {
+            res.push(TargetDep {
+                name: name.to_string(),
+                file: dep.script_path(),
+                line: dep.script_line,
+            })
+        }
+    }
+    res
+}
+
+/// checks that the targets don't depend on themselves, directly or through other targets
+fn check_cycles(block: &GenBlockTup, targets: &[String]) -> Result<(), ScriptError> {
+    let mut visited = Vec::new();
+    for name in targets {
+        let Some(target) = block.get_target(name) else {
+            continue;
+        };
+        let mut path = Vec::new();
+        if let Some(cycle) = find_cycle(&target, &mut path, &mut visited)
+            && let Some(((last, closing), deps)) = cycle.split_last()
+        {
+            let mut msg = format!(
+                "Dependency cycle {} -> {}",
+                cycle
+                    .iter()
+                    .map(|(target, _)| target.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" -> "),
+                cycle[0].0
+            );
+            // the dependency closing the cycle is the location of the error
+            for (target, dep) in deps {
+                msg += &format!("\n  {} depends on {} at {}:{}", target, dep.name, dep.file, dep.line)
+            }
+            msg += &format!("\n  {} depends on {}", last, closing.name);
+            return Err(ScriptError {
+                file: closing.file.clone(),
+                line: closing.line,
+                column: None,
+                msg,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// the path keeps targets with their dependencies currently followed, a cycle is returned
+/// as a part of the path starting with the repeated target
+fn find_cycle(
+    target: &GenBlockTup,
+    path: &mut Vec<(String, TargetDep)>,
+    visited: &mut Vec<String>,
+) -> Option<Vec<(String, TargetDep)>> {
+    let name = target.borrow().name.clone().unwrap_or_default();
+    if let Some(pos) = path.iter().position(|(target, _)| *target == name) {
+        return Some(path.split_off(pos));
+    }
+    if visited.contains(&name) {
+        return None;
+    }
+    visited.push(name.clone());
+    for dep in target_deps(target) {
+        let Some(dep_target) = target.get_target(&dep.name) else {
+            continue;
+        };
+        path.push((name.clone(), dep));
+        if let Some(cycle) = find_cycle(&dep_target, path, visited) {
+            return Some(cycle);
+        }
+        path.pop();
+    }
+    None
+}
+
+pub fn set_target_result(name: &str, res: Result<bool, TargetFailure>) {
+    let mut results = TARGET_RESULTS.write().unwrap();
+    match results.iter_mut().find(|(target, _)| target == name) {
+        Some((_, known)) => *known = res,
+        None => results.push((name.to_string(), res)),
+    }
+}
+
+pub fn get_target_result(name: &str) -> Option<Result<bool, TargetFailure>> {
+    let results = TARGET_RESULTS.read().unwrap();
+    results.iter().find(|(target, _)| target == name).map(|(_, res)| res.clone())
+}
+
+/// failed and skipped targets of the invocation
+fn target_failures() -> Vec<(String, TargetFailure)> {
+    let results = TARGET_RESULTS.read().unwrap();
+    results
+        .iter()
+        .filter_map(|(target, res)| Some((target.to_string(), res.clone().err()?)))
+        .collect()
+}
+
+/// a failure of a target skipped because of one or several failed dependencies
+fn combined_failure(failures: Vec<TargetFailure>) -> Option<TargetFailure> {
+    let mut targets: Vec<&str> = Vec::new();
+    let mut reasons: Vec<&str> = Vec::new();
+    for failure in &failures {
+        for target in failure.target.split(", ") {
+            if !targets.contains(&target) {
+                targets.push(target);
+                reasons.push(&failure.reason)
+            }
+        }
+    }
+    if targets.is_empty() {
+        return None;
+    }
+    Some(TargetFailure {
+        target: targets.join(", "),
+        reason: reasons.join("; "),
+    })
+}
+
+/// prints failed and skipped targets of a keep going build
+fn build_summary(log: &Log, failures: &[(String, TargetFailure)]) -> Result<(), Box<dyn Error>> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+    log.message(&"Build summary".bold().to_string());
+    for (target, failure) in failures {
+        if failure.target == *target {
+            log.message(&format!("  {} {} - {}", "failed:".red(), target, failure.reason))
+        }
+    }
+    for (target, failure) in failures {
+        if failure.target != *target {
+            log.message(&format!("  {} {} because of {}", "skipped:".yellow(), target, failure.target))
+        }
+    }
+    let failed = failures.iter().filter(|(target, failure)| failure.target == *target).count();
+    Err(Box::new(BuildFailure(format!(
+        "{} target(s) failed",
+        failed
+    ))))
+}
+
+/// runs RustBee with the same arguments again every time when inputs of evaluated
+/// dependencies, the script, or its includes change
+pub fn watch(log: &Log, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let exe = env::current_exe()?;
+    let list = env::temp_dir()
+        .join(format!("rb-watch-{}", std::process::id()))
+        .display()
+        .to_string();
+    // arguments after -- belong to the script
+    let script_args = args.iter().position(|arg| arg == "--").unwrap_or(args.len());
+    let mut run_args: Vec<&String> = Vec::new();
+    let mut options = args[1..script_args].iter();
+    while let Some(arg) = options.next() {
+        if arg == "-l" || arg.starts_with("--logfile") {
+            // the log file is written by the watching process only, the output of runs is teed in it
+            options.next();
+        } else if arg != "--watch" {
+            run_args.push(arg)
+        }
+    }
+    run_args.extend(&args[script_args..]);
+    loop {
+        let _ = fs::remove_file(&list);
+        let mut command = Command::new(&exe);
+        command.arg("--watch-list").arg(&list).args(&run_args);
+        let status = if log.is_teed() {
+            status_teed(log, &mut command)?
+        } else {
+            command.status()?
+        };
+        log.log(&format!("run completed: {status}"));
+        let mut files: Vec<String> = fs::read_to_string(&list)
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        files.sort();
+        files.dedup();
+        let _ = fs::remove_file(&list);
+        log.message(&format!(
+            "{} {} file(s) for changes, press Ctrl-C to stop",
+            "Watching".bold(),
+            files.len()
+        ));
+        log.debug(&format!("watched: {files:?}"));
+        let snapshot = |files: &[String]| -> Vec<Option<SystemTime>> {
+            files.iter().map(|file| newest(file)).collect()
+        };
+        let last = snapshot(&files);
+        let mut current = last.clone();
+        while current == last {
+            std::thread::sleep(Duration::from_millis(500));
+            current = snapshot(&files)
+        }
+        // wait while files are still changing, as an editor saving several of them
+        loop {
+            std::thread::sleep(Duration::from_millis(300));
+            let next = snapshot(&files);
+            if next == current {
+                break;
+            }
+            current = next
+        }
+    }
+}
+
+pub fn set_watch_list(file: &str) {
+    *WATCH_LIST.write().unwrap() = Some((file.to_string(), Vec::new()))
+}
+
+/// adds a file or a mask to the list of watched inputs, when the watch mode is on
+pub fn watch_file(path: &str) {
+    let mut list = WATCH_LIST.write().unwrap();
+    let Some((list_file, watched)) = list.as_mut() else {
+        return;
+    };
+    if watched.iter().any(|watched| watched == path) {
+        return;
+    }
+    watched.push(path.to_string());
+    // the list is shared with jobs of a parallel build
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(list_file) {
+        let _ = writeln!(file, "{path}");
+    }
+}
+
+/// reports a failure of the currently executed target, only first failure is kept
+pub fn report_failure(reason: String) {
+    let mut failure = FAILURE.write().unwrap();
+    if failure.is_none() {
+        *failure = Some(reason)
+    }
+}
+
+/// reports an error in the script, it fails the currently executed target and stops the build
+pub fn report_script_error(err: ScriptError) {
+    report_failure(err.to_string());
+    let mut error = SCRIPT_ERROR.write().unwrap();
+    if error.is_none() {
+        *error = Some(err)
+    }
+}
+
+fn take_script_error() -> Option<ScriptError> {
+    SCRIPT_ERROR.write().unwrap().take()
+}
+
+pub fn failure_pending() -> bool {
+    FAILURE.read().unwrap().is_some()
+}
+
+/// numbers of a range as from..to, the end isn't included
+fn numeric_range(spec: &str) -> Option<Vec<String>> {
+    let (from, to) = spec.trim().split_once("..")?;
+    let (from, to) = (from.trim().parse::<i64>().ok()?, to.trim().parse::<i64>().ok()?);
+    Some((from..to).map(|num| num.to_string()).collect())
+}
+
+/// checks if executing of the current block sequence has to be stopped
+fn interrupted() -> bool {
+    failure_pending() || JUMP.read().unwrap().is_some()
+}
+
+fn take_jump() -> Option<Jump> {
+    JUMP.write().unwrap().take()
+}
+
+/// checks if a loop goes to the next iteration, break() and continue() are taken by the loop
+fn loop_continues() -> bool {
+    if failure_pending() {
+        return false;
+    }
+    let mut jump = JUMP.write().unwrap();
+    match *jump {
+        None => true,
+        Some(Jump::Continue) => {
+            *jump = None;
+            true
+        }
+        Some(Jump::Break) => {
+            *jump = None;
+            false
+        }
+        Some(Jump::Return | Jump::Stop) => false,
+    }
+}
+
+fn take_failure() -> Option<String> {
+    FAILURE_ORIGIN.write().unwrap().take();
+    FAILURE.write().unwrap().take()
+}
+
+fn restore_failure(failure: Option<String>) {
+    *FAILURE.write().unwrap() = failure
+}
+
+/// logs an error of a function, in a 'try' block the error fails the block instead, so it can be caught
+fn function_error(log: &Log, msg: String) {
+    if TRY_DEPTH.load(Ordering::Relaxed) > 0 {
+        report_failure(msg)
+    } else {
+        log.error(&msg)
+    }
+}
+
+/// a signal which terminated a process, signals are known only on Unix
+fn exit_signal(status: &ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+/// runs a command copying its stdout and stderr in the console and log sinks
+fn status_teed(log: &Log, command: &mut Command) -> io::Result<ExitStatus> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    std::thread::scope(|scope| {
+        if let Some(stdout) = stdout {
+            scope.spawn(|| {
+                tee_lines(stdout, |line| {
+                    println!("{line}");
+                    log.tee(line)
+                })
+            });
+        }
+        if let Some(stderr) = stderr {
+            scope.spawn(|| {
+                tee_lines(stderr, |line| {
+                    eprintln!("{line}");
+                    log.tee(line)
+                })
+            });
+        }
+    });
+    child.wait()
+}
+
+fn tee_lines(stream: impl Read, out: impl Fn(&str)) {
+    let mut reader = BufReader::new(stream);
+    let mut buf = Vec::new();
+    while let Ok(len) = reader.read_until(b'\n', &mut buf) {
+        if len == 0 {
+            break;
+        }
+        let line = String::from_utf8_lossy(&buf);
+        out(line.trim_end_matches(['\n', '\r']));
+        buf.clear()
+    }
 }
 
 fn no_parameters(fun: &GenBlock) -> bool {
@@ -2803,10 +4133,6 @@ fn no_parameters(fun: &GenBlock) -> bool {
     fun.block_type == BlockType::Function && fun.params.is_empty()
 }
 
-pub fn timestamp(p: &str) -> Option<String> {
-    Some(format_system_time(fs::metadata(p).ok()?.modified().ok()?))
-}
-
 pub fn format_system_time(time: SystemTime) -> String {
     let dur = time
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -2816,7 +4142,140 @@ pub fn format_system_time(time: SystemTime) -> String {
     format!("{:0>2}{:0>2}{:0>2}T{:0>2}{:0>2}{:0>2}Z", y, m, d, h, min, s) // see ISO 8601
 }
 
+/// checks if contents of input and output files changed since the target was executed
+/// last time, the reason of the change is returned too
+fn exec_changed(block: &GenBlockTup, p1: &str, p2: &str) -> (bool, String) {
+    let (dir, key) = changed_key(block, p1, p2);
+    CHANGED_STATES.write().unwrap().push(ChangedState {
+        dir: dir.clone(),
+        key: key.clone(),
+        inputs: cwd_path(block, p1),
+        outputs: cwd_path(block, p2),
+        location: format!("{}:{}", block.borrow().script_path(), block.borrow().script_line),
+    });
+    watch_file(&cwd_path(block, p1));
+    let inputs = mask_files(&cwd_path(block, p1));
+    let outputs = mask_files(&cwd_path(block, p2));
+    if outputs.is_empty() {
+        return (true, format!("no output files {p2} exist"));
+    }
+    let current = state::hash_files(&[inputs, outputs].concat());
+    let Some(recorded) = state::load(&dir, &key) else {
+        return (true, "no state was recorded".to_string());
+    };
+    for (file, hash) in &current {
+        match recorded.iter().find(|(recorded_file, _)| recorded_file == file) {
+            None => return (true, format!("{file} was added")),
+            Some((_, recorded_hash)) if recorded_hash != hash => {
+                return (true, format!("{file} was changed"));
+            }
+            _ => (),
+        }
+    }
+    if let Some((file, _)) = recorded
+        .iter()
+        .find(|(file, _)| !current.iter().any(|(current_file, _)| current_file == file))
+    {
+        return (true, format!("{file} was removed"));
+    }
+    (false, format!("{} file(s) weren't changed", current.len()))
+}
+
+/// checks if any prerequisite listed in the dependency file is newer than the output,
+/// a missing dependency file, output, or prerequisite requires the rebuild
+fn exec_depinfo(block: &GenBlockTup, p1: &str, p2: &str) -> (bool, String) {
+    let Ok(content) = fs::read_to_string(cwd_path(block, p1)) else {
+        return (true, format!("the dependency file {p1} can't be read"));
+    };
+    let prerequisites = util::parse_dep_info(&content);
+    for prerequisite in &prerequisites {
+        watch_file(&cwd_path(block, prerequisite))
+    }
+    let Some(output_time) = newest(&cwd_path(block, p2)) else {
+        return (true, format!("the output {p2} doesn't exist"));
+    };
+    for prerequisite in &prerequisites {
+        match last_modified(&cwd_path(block, prerequisite)) {
+            None => return (true, format!("{prerequisite} doesn't exist")),
+            Some(time) if time > output_time => {
+                return (true, format!("{prerequisite} is newer than {p2}"));
+            }
+            _ => (),
+        }
+    }
+    (
+        false,
+        format!("{} prerequisite(s) aren't newer than {p2}", prerequisites.len()),
+    )
+}
+
+/// stores content hashes of files of all 'changed' dependencies of the executed target
+fn record_changed(log: &Log, from: usize, store: bool) {
+    let states: Vec<ChangedState> = {
+        let mut states = CHANGED_STATES.write().unwrap();
+        let from = from.min(states.len());
+        states.drain(from..).collect()
+    };
+    if !store {
+        return;
+    }
+    for (pos, changed) in states.iter().enumerate() {
+        // the same 'changed' can be evaluated several times
+        if states[pos + 1..].iter().any(|later| later.dir == changed.dir && later.key == changed.key) {
+            continue;
+        }
+        let files = [mask_files(&changed.inputs), mask_files(&changed.outputs)].concat();
+        if let Err(err) = state::save(&changed.dir, &changed.key, &state::hash_files(&files)) {
+            log.error(&format!(
+                "Can't store the build state in {}, problem: {err} at {}: ",
+                changed.dir, changed.location
+            ))
+        }
+    }
+}
+
+/// the state directory and the key of a 'changed' dependency state
+fn changed_key(block: &GenBlockTup, p1: &str, p2: &str) -> (String, String) {
+    let target = block.target_name();
+    let dir = block
+        .get_top_block()
+        .search_up(CWD)
+        .map(|cwd| cwd.into_text())
+        .unwrap_or_default();
+    (dir, format!("{target}\t{p1}\t{p2}"))
+}
+
+fn cwd_path(block: &GenBlockTup, path: &str) -> String {
+    match block.search_up(CWD) {
+        Some(cwd) if !has_root(path) => cwd.into_text() + MAIN_SEPARATOR_STR + path,
+        _ => path.to_string(),
+    }
+}
+
+/// the newest input and output files of anynewer with their modification times
+fn anynewer_evidence(block: &GenBlockTup, p1: &str, p2: &str) -> (String, String) {
+    let cwd = block.search_up(CWD).map(|cwd| cwd.into_text()).unwrap_or_default();
+    let describe = |mask: &str| {
+        let mask = if has_root(mask) || cwd.is_empty() {
+            mask.to_string()
+        } else {
+            cwd.clone() + MAIN_SEPARATOR_STR + mask
+        };
+        match newest_file(&mask) {
+            Some((file, time)) => {
+                // milliseconds matter when files are modified in the same second
+                let time_str = format_system_time(time);
+                let millis = time.duration_since(UNIX_EPOCH).unwrap_or_default().subsec_millis();
+                format!("{file} modified {}.{millis:03}Z", time_str.trim_end_matches('Z'))
+            }
+            None => format!("{mask} doesn't exist"),
+        }
+    };
+    (describe(p1), describe(p2))
+}
+
 pub fn exec_anynewer(block: &GenBlockTup, p1: &String, p2: &String) -> bool {
+    watch_file(&cwd_path(block, p1));
     let Some(cwd) = block.search_up(CWD) else {
         // unlikely ~cwd~ isn't set
         return newest(p1) > newest(p2);
@@ -2825,12 +4284,12 @@ pub fn exec_anynewer(block: &GenBlockTup, p1: &String, p2: &String) -> bool {
     let t1 = if has_root(p1) {
         newest(p1)
     } else {
-        newest(&(cwd.value.clone() + MAIN_SEPARATOR_STR + p1))
+        newest(&(cwd.text() + MAIN_SEPARATOR_STR + p1))
     };
     let t2 = if has_root(p2) {
         newest(p2)
     } else {
-        newest(&(cwd.value + MAIN_SEPARATOR_STR + p2))
+        newest(&(cwd.into_text() + MAIN_SEPARATOR_STR + p2))
     };
     //println!{"modified {:?} and {:?}", t1, t2};
     t1 > t2
@@ -2900,6 +4359,11 @@ fn find_newer(dir1: &str, ext1: &str, dir2: &Option<String>, ext2: &Option<Strin
 }
 
 pub fn newest(mask: &str) -> Option<SystemTime> {
+    newest_file(mask).map(|(_, time)| time)
+}
+
+/// the newest file matching the mask, and its modification time
+pub fn newest_file(mask: &str) -> Option<(String, SystemTime)> {
     //println!{"find newest in {mask}"}
     let path = Path::new(mask);
     let parent = path.parent()?;
@@ -2908,34 +4372,26 @@ pub fn newest(mask: &str) -> Option<SystemTime> {
     let str_name = path.file_name()?.display().to_string();
     if let Some((start, end)) = str_name.split_once('*') {
         // only first * meaningful
-        let mut last: Option<SystemTime> = None;
+        let mut last: Option<(String, SystemTime)> = None;
         let dir = parent.read_dir().ok()?;
         let mask_len = start.len() + end.len();
         for entry in dir {
             let entry = entry.ok()?;
             let file_type = entry.file_type().ok()?;
             let name = entry.file_name().display().to_string();
-            if file_type.is_file()
-                && name.len() >= mask_len
-                && (!cfg!(windows) && name.starts_with(start) && name.ends_with(end)
-                    || cfg!(windows)
-                        && winops::eq_str_ascii_ignorecase(start, &name[0..start.len()])
-                        && winops::eq_ascii_ignorecase(
-                            end.as_bytes(),
-                            &name.as_bytes()[name.len() - end.len()..],
-                        ))
+            if file_type.is_file() && name.len() >= mask_len && mask_matches(&name, start, end)
                 || file_type.is_dir()
             {
                 let dir_entry_path = entry.path().display().to_string();
                 let current_last = if file_type.is_dir() {
-                    newest(&format! {"{dir_entry_path}{MAIN_SEPARATOR}{str_name}"})
+                    newest_file(&format! {"{dir_entry_path}{MAIN_SEPARATOR}{str_name}"})
                 } else {
-                    last_modified(&dir_entry_path)
+                    last_modified(&dir_entry_path).map(|time| (dir_entry_path, time))
                 };
                 match last {
                     None => last = current_last,
-                    Some(time) => {
-                        if let Some(time2) = current_last
+                    Some((_, time)) => {
+                        if let Some((_, time2)) = current_last
                             && time2 > time
                         {
                             last = current_last;
@@ -2946,8 +4402,53 @@ pub fn newest(mask: &str) -> Option<SystemTime> {
         }
         last
     } else {
-        last_modified(&path.display().to_string())
+        let file = path.display().to_string();
+        last_modified(&file).map(|time| (file, time))
+    }
+}
+
+/// all files matching the mask, the mask can include one '*' in a file name,
+/// then files are also searched in subdirectories
+pub fn mask_files(mask: &str) -> Vec<String> {
+    let mut res = Vec::new();
+    let path = Path::new(mask);
+    let (Some(parent), Some(str_name)) = (path.parent(), path.file_name()) else {
+        return res;
+    };
+    let str_name = str_name.display().to_string();
+    let Some((start, end)) = str_name.split_once('*') else {
+        if path.is_file() {
+            res.push(mask.to_string())
+        }
+        return res;
+    };
+    let Ok(dir) = parent.read_dir() else {
+        return res;
+    };
+    for entry in dir.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let name = entry.file_name().display().to_string();
+        let dir_entry_path = entry.path().display().to_string();
+        if file_type.is_dir() {
+            res.extend(mask_files(&format! {"{dir_entry_path}{MAIN_SEPARATOR}{str_name}"}))
+        } else if file_type.is_file()
+            && name.len() >= start.len() + end.len()
+            && mask_matches(&name, start, end)
+        {
+            res.push(dir_entry_path)
+        }
     }
+    res.sort();
+    res
+}
+
+fn mask_matches(name: &str, start: &str, end: &str) -> bool {
+    !cfg!(windows) && name.starts_with(start) && name.ends_with(end)
+        || cfg!(windows)
+            && winops::eq_str_ascii_ignorecase(start, &name[0..start.len()])
+            && winops::eq_ascii_ignorecase(end.as_bytes(), &name.as_bytes()[name.len() - end.len()..])
 }
 
 pub fn last_modified(file: &str) -> Option<SystemTime> {
@@ -0,0 +1,209 @@
+// the graph of script targets and their dependencies, it's exported in DOT or JSON
+use crate::fun::{BlockType, GenBlock, GenBlockTup};
+use crate::json::json_str;
+
+struct Node {
+    id: String,
+    kind: String,
+    label: String,
+    description: String,
+    location: String,
+}
+
+struct Edge {
+    from: String,
+    to: String,
+    kind: String,
+    location: String,
+}
+
+#[derive(Default)]
+pub struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    count: usize, // for generating ids of expression nodes
+}
+
+impl Graph {
+    /// builds the graph of all targets of the script tree
+    pub fn new(block: &GenBlockTup) -> Graph {
+        let mut graph = Graph::default();
+        let targets: Vec<GenBlockTup> = block
+            .borrow()
+            .children
+            .iter()
+            .filter(|ch| ch.borrow().block_type == BlockType::Target)
+            .cloned()
+            .collect();
+        for target in &targets {
+            let target = target.borrow();
+            let name = target.name.clone().unwrap_or_default();
+            graph.add_node(
+                format!("target:{name}"),
+                "target",
+                &name,
+                target.flex.as_deref().unwrap_or_default(),
+                block_location(&target),
+            );
+        }
+        for target in &targets {
+            let target = target.borrow();
+            let from = format!("target:{}", target.name.as_deref().unwrap_or_default());
+            for dep in &target.deps {
+                let dep = dep.borrow();
+                if dep.children.is_empty() {
+                    let id = graph.next_id("value");
+                    let value = dep.out.as_deref().unwrap_or("true");
+                    graph.add_node(id.clone(), "value", value, "", block_location(&dep));
+                    graph.add_edge(&from, &id, "dependency", block_location(&dep));
+                }
+                for child in &dep.children {
+                    graph.add_expr(&from, child, block_location(&dep))
+                }
+            }
+        }
+        graph
+    }
+
+    fn add_expr(&mut self, from: &str, block: &GenBlockTup, location: String) {
+        let block = block.borrow();
+        match block.block_type {
+            BlockType::Function => match block.name.as_deref() {
+                Some("target") => {
+                    let name = block.params.first().cloned().unwrap_or_default();
+                    let id = format!("target:{name}");
+                    // an undefined target is shown with no location
+                    self.add_node(id.clone(), "target", &name, "", String::new());
+                    self.add_edge(from, &id, "target", location)
+                }
+                Some("anynewer" | "changed" | "depinfo") => {
+                    for (param, kind) in block.params.iter().zip(["input", "output"]) {
+                        let id = format!("file:{param}");
+                        self.add_node(id.clone(), kind, param, "", String::new());
+                        self.add_edge(from, &id, kind, location.clone())
+                    }
+                }
+                name => {
+                    let id = self.next_id("function");
+                    let label = format!(
+                        "{}({})",
+                        name.unwrap_or_default(),
+                        block.params.join(",")
+                    );
+                    self.add_node(id.clone(), "function", &label, "", block_location(&block));
+                    self.add_edge(from, &id, "dependency", location)
+                }
+            },
+            _ => {
+                let kind = format!("{:?}", block.block_type).to_lowercase();
+                let id = self.next_id(&kind);
+                self.add_node(id.clone(), &kind, &kind, "", block_location(&block));
+                self.add_edge(from, &id, "dependency", location);
+                for child in &block.children {
+                    let location = block_location(&child.borrow());
+                    self.add_expr(&id, child, location)
+                }
+            }
+        }
+    }
+
+    fn next_id(&mut self, kind: &str) -> String {
+        self.count += 1;
+        format!("{kind}:{}", self.count)
+    }
+
+    /// a node with the same id is added only once
+    fn add_node(&mut self, id: String, kind: &str, label: &str, description: &str, location: String) {
+        if self.nodes.iter().any(|node| node.id == id) {
+            return;
+        }
+        self.nodes.push(Node {
+            id,
+            kind: kind.to_string(),
+            label: label.to_string(),
+            description: description.to_string(),
+            location,
+        })
+    }
+
+    fn add_edge(&mut self, from: &str, to: &str, kind: &str, location: String) {
+        self.edges.push(Edge {
+            from: from.to_string(),
+            to: to.to_string(),
+            kind: kind.to_string(),
+            location,
+        })
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut res = String::from("digraph targets {\n");
+        for node in &self.nodes {
+            let shape = match node.kind.as_str() {
+                "target" => "box",
+                "input" | "output" => "note",
+                "function" | "value" => "ellipse",
+                _ => "diamond",
+            };
+            res += &format!(
+                "    {} [label={}, shape={shape}, tooltip={}];\n",
+                dot_str(&node.id),
+                dot_str(&node.label),
+                dot_str(&node.location)
+            );
+        }
+        for edge in &self.edges {
+            res += &format!(
+                "    {} -> {} [label={}, tooltip={}];\n",
+                dot_str(&edge.from),
+                dot_str(&edge.to),
+                dot_str(&edge.kind),
+                dot_str(&edge.location)
+            );
+        }
+        res.push_str("}\n");
+        res
+    }
+
+    pub fn to_json(&self) -> String {
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                format!(
+                    "    {{\"id\": {}, \"kind\": {}, \"label\": {}, \"description\": {}, \"location\": {}}}",
+                    json_str(&node.id),
+                    json_str(&node.kind),
+                    json_str(&node.label),
+                    json_str(&node.description),
+                    json_str(&node.location)
+                )
+            })
+            .collect();
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                format!(
+                    "    {{\"from\": {}, \"to\": {}, \"kind\": {}, \"location\": {}}}",
+                    json_str(&edge.from),
+                    json_str(&edge.to),
+                    json_str(&edge.kind),
+                    json_str(&edge.location)
+                )
+            })
+            .collect();
+        format!(
+            "{{\n  \"nodes\": [\n{}\n  ],\n  \"edges\": [\n{}\n  ]\n}}\n",
+            nodes.join(",\n"),
+            edges.join(",\n")
+        )
+    }
+}
+
+fn block_location(block: &GenBlock) -> String {
+    format!("{}:{}", block.script_path(), block.script_line)
+}
+
+fn dot_str(str: &str) -> String {
+    format!("\"{}\"", str.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,248 @@
+// a minimal JSON support for the language server and the graph export
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser { chars: text.chars().collect(), pos: 0 };
+        let res = parser.value()?;
+        parser.blanks();
+        if parser.pos < parser.chars.len() {
+            return Err(format!("Unexpected symbol at {}", parser.pos));
+        }
+        Ok(res)
+    }
+
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(key, val)| (key.to_string(), val)).collect())
+    }
+
+    pub fn str(str: impl Into<String>) -> Json {
+        Json::Str(str.into())
+    }
+
+    /// a field of an object
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, val)| val),
+            _ => None,
+        }
+    }
+
+    /// a value by the path of object fields
+    pub fn at(&self, path: &[&str]) -> Option<&Json> {
+        path.iter().try_fold(self, |json, key| json.get(key))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(str) => Some(str),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            Json::Number(num) if *num >= 0.0 => Some(*num as u32),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Json>> {
+        match self {
+            Json::Array(vals) => Some(vals),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(val) => write!(f, "{val}"),
+            Json::Number(num) => write!(f, "{num}"),
+            Json::Str(str) => write!(f, "{}", json_str(str)),
+            Json::Array(vals) => {
+                write!(f, "[")?;
+                for (i, val) in vals.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?
+                    }
+                    write!(f, "{val}")?
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, val)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?
+                    }
+                    write!(f, "{}:{val}", json_str(key))?
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+pub fn json_str(str: &str) -> String {
+    let mut res = String::with_capacity(str.len() + 2);
+    res.push('"');
+    for c in str.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn blanks(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1
+        }
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        let c = self.chars.get(self.pos).copied().ok_or("Unexpected end of JSON")?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), String> {
+        for expected in word.chars() {
+            if self.next()? != expected {
+                return Err(format!("Expected {word} at {}", self.pos));
+            }
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.blanks();
+        match self.chars.get(self.pos) {
+            Some('n') => self.expect("null").map(|_| Json::Null),
+            Some('t') => self.expect("true").map(|_| Json::Bool(true)),
+            Some('f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some('"') => self.string().map(Json::Str),
+            Some('[') => {
+                self.pos += 1;
+                let mut vals = Vec::new();
+                self.blanks();
+                if self.chars.get(self.pos) == Some(&']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(vals));
+                }
+                loop {
+                    vals.push(self.value()?);
+                    self.blanks();
+                    match self.next()? {
+                        ',' => (),
+                        ']' => return Ok(Json::Array(vals)),
+                        c => return Err(format!("Unexpected symbol {c:?} in array at {}", self.pos)),
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                self.blanks();
+                if self.chars.get(self.pos) == Some(&'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.blanks();
+                    let key = self.string()?;
+                    self.blanks();
+                    if self.next()? != ':' {
+                        return Err(format!("Expected ':' at {}", self.pos));
+                    }
+                    fields.push((key, self.value()?));
+                    self.blanks();
+                    match self.next()? {
+                        ',' => (),
+                        '}' => return Ok(Json::Object(fields)),
+                        c => return Err(format!("Unexpected symbol {c:?} in object at {}", self.pos)),
+                    }
+                }
+            }
+            Some(_) => {
+                let start = self.pos;
+                while self
+                    .chars
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+                {
+                    self.pos += 1
+                }
+                let num: String = self.chars[start..self.pos].iter().collect();
+                num.parse().map(Json::Number).map_err(|_| format!("Invalid number at {start}"))
+            }
+            None => Err("Unexpected end of JSON".to_string()),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.next()? != '"' {
+            return Err(format!("Expected a string at {}", self.pos));
+        }
+        let mut res = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(res),
+                '\\' => match self.next()? {
+                    'n' => res.push('\n'),
+                    'r' => res.push('\r'),
+                    't' => res.push('\t'),
+                    'b' => res.push('\u{8}'),
+                    'f' => res.push('\u{c}'),
+                    'u' => {
+                        let mut code = self.hex()?;
+                        // a surrogate pair
+                        if (0xd800..0xdc00).contains(&code) && self.chars.get(self.pos) == Some(&'\\') {
+                            self.pos += 1;
+                            self.expect("u")?;
+                            code = 0x10000 + ((code - 0xd800) << 10) + (self.hex()?.wrapping_sub(0xdc00) & 0x3ff);
+                        }
+                        res.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+                    }
+                    c => res.push(c),
+                },
+                c => res.push(c),
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.next()?.to_digit(16).ok_or(format!("Invalid escape at {}", self.pos))?;
+            code = code * 16 + digit
+        }
+        Ok(code)
+    }
+}
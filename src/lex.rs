@@ -6,10 +6,13 @@ use std::{fs::File,
           cell::RefCell,
           rc::Rc,
           error::Error,
+          fmt, cmp,
+          time::SystemTime,
+          sync::RwLock,
     };
 use crate::log::Log;
 use crate::fun::{GenBlock, BlockType, GenBlockTup};
-use crate::fun::PREV_VAL;
+use crate::fun::{PREV_VAL, format_system_time};
 use crate::get_property;
 use crate::util::{vec_to_str};
 
@@ -17,6 +20,9 @@ const BUF_SIZE: usize = 256;
 
 const MAX_LEX_LEN: usize = 16_384;
 
+// errors the lexer reported and continued after, a static check counts them
+static RECOVERED_ERRORS: RwLock<Vec<ScriptError>> = RwLock::new(Vec::new());
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq, Default)]
 pub enum VarType {
@@ -25,12 +31,8 @@ pub enum VarType {
     Property,
     Directory,
     Path,
-    Array,
     File,
     Environment,
-    Number,
-    Date,
-    Bool,
     Eval,
     Function,
     Url,
@@ -39,7 +41,7 @@ pub enum VarType {
 }
 
 #[allow(dead_code)]
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum Lexem {
     Variable(String), 
     Value(String), 
@@ -98,8 +100,6 @@ enum LexState {
 enum TemplateState {
     InVal,
     VarStart,  // $
-    LeftBrack,
-    RightBrack,
     InVar,
 }
 
@@ -120,46 +120,90 @@ enum HdrState {
     InWorkQt,
 }
  
+/// a content of a variable, a script literal stays a string and gets its type at a comparison
+#[derive(Debug, Clone)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Path(PathBuf),
+    Array(Vec<String>),
+    Map(Vec<(String, String)>), // keeps an order of keys
+    Date(SystemTime),
+}
+
 #[derive(Debug, Clone)]
 pub struct VarVal {
-    pub val_type: VarType,
-    pub value: String, // TODO make it enum based on type
-    pub values: Vec<String>, // TODO make it Option<Vec<String>>
+    pub val_type: VarType, // how a value is resolved, as an environment variable or a property name
+    pub value: Value,
 }
 
+/// an error in a script, a column is unknown for errors found at the script execution
+#[derive(Debug, Clone)]
+pub struct ScriptError {
+    pub file: String,
+    pub line: u32,
+    pub column: Option<u16>,
+    pub msg: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "{} at {}:{}:{}", self.msg, self.file, self.line, column),
+            None => write!(f, "{} at {}:{}", self.msg, self.file, self.line)
+        }
+    }
+}
+
+impl Error for ScriptError {}
+
 pub struct Reader {
     buf: [u8;BUF_SIZE],
     pos: usize,
     end: usize,
     line: u32,
     line_offset: u16,
-    reader: File,
+    reader: Box<dyn Read>,
     file_path: String,
 }
 
 impl VarVal {
     pub fn from_string(str: impl Into<String>) -> VarVal {
-        VarVal{val_type: VarType::Generic, value: str.into(), values: Vec::new()}  
+        VarVal{val_type: VarType::Generic, value: Value::Str(str.into())}
     }
     
     pub fn from_path(path: &Path) -> VarVal {
-        VarVal{val_type: VarType::Generic, value: path.display().to_string(), values: Vec::new()}  
+        VarVal{val_type: VarType::Generic, value: Value::Path(path.to_path_buf())}
     }
 
     pub fn from_bool(boole: bool) -> VarVal {
-        VarVal{val_type: VarType::Bool, value: if boole {"true".to_string()} else {"false".to_string()}, values: Vec::new()}  
+        VarVal{val_type: VarType::Generic, value: Value::Bool(boole)}
     }
 
     pub fn from_i32(number: i32) -> VarVal {
-        VarVal{val_type: VarType::Number, value: format!{"{}", number}, values: Vec::new()}  // 
+        VarVal::from_i64(number as i64)
+    }
+
+    pub fn from_i64(number: i64) -> VarVal {
+        VarVal{val_type: VarType::Generic, value: Value::Int(number)}
     }
 
     pub fn from_f64(number: f64) -> VarVal {
-        VarVal{val_type: VarType::Number, value: format!{"{number}"}, values: Vec::new()}  // 
+        VarVal{val_type: VarType::Generic, value: Value::Float(number)}
+    }
+
+    pub fn from_date(time: SystemTime) -> VarVal {
+        VarVal{val_type: VarType::Generic, value: Value::Date(time)}
+    }
+
+    pub fn from_map(entries: Vec<(String, String)>) -> VarVal {
+        VarVal{val_type: VarType::Generic, value: Value::Map(entries)}
     }
 
     pub fn from_vec(vec: Vec<String>) -> VarVal {
-        VarVal{val_type: VarType::Array, value: String::new(), values: vec}  
+        VarVal{val_type: VarType::Generic, value: Value::Array(vec)}
     }
     
     pub fn from_iter<'a>(iter: impl Iterator<Item = &'a str>) -> VarVal {
@@ -167,28 +211,198 @@ impl VarVal {
         for el in iter {
             vec.push(el.to_string())
         }
-        VarVal{val_type: VarType::Array, value: String::new(), values: vec}  
+        VarVal::from_vec(vec)
     }
 
-    pub fn is_true(& self) -> bool {
+    /// the same value under another type of the resolution
+    pub fn with_type(&self, val_type: VarType) -> VarVal {
+        VarVal{val_type, value: self.value.clone()}
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self.value, Value::Array(_))
+    }
+
+    /// elements of an array, nothing for other values
+    pub fn values(&self) -> &[String] {
+        match &self.value {
+            Value::Array(values) => values,
+            _ => &[]
+        }
+    }
+
+    pub fn is_map(&self) -> bool {
+        matches!(self.value, Value::Map(_))
+    }
+
+    /// a value of a map key
+    pub fn entry(&self, key: &str) -> Option<&String> {
+        match &self.value {
+            Value::Map(entries) => entries.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None
+        }
+    }
+
+    pub fn into_values(self) -> Vec<String> {
+        match self.value {
+            Value::Array(values) => values,
+            _ => Vec::new()
+        }
+    }
+
+    /// a value as a string, as it was stored, without resolving an environment variable or a property
+    pub fn text(&self) -> String {
+        self.value.to_string()
+    }
+
+    pub fn into_text(self) -> String {
+        match self.value {
+            Value::Str(str) => str,
+            value => value.to_string()
+        }
+    }
+
+    /// a value with resolved an environment variable or a property
+    pub fn resolved(&self) -> Value {
         match self.val_type {
-            VarType::Environment  => {
-                match env::var(&self.value) {
-                    Ok(val) => val == "true",
-                    Err(_e) => self.value == "true"
-                }
+            VarType::Environment => env::var(self.text()).map(Value::Str).unwrap_or(self.value.clone()),
+            VarType::Property => get_property(&self.text()).map(Value::Str).unwrap_or(self.value.clone()),
+            _ => self.value.clone()
+        }
+    }
+
+    pub fn is_true(& self) -> bool {
+        match self.resolved() {
+            Value::Str(str) => match self.val_type {
+                VarType::Environment | VarType::Property => str == "true",
+                _ => !str.is_empty() // consider adding interpolation
             },
-            VarType::Property => {
-                if let Some(val) = get_property(&self.value) {
-                    val == "true"
+            Value::Int(number) => number != 0,
+            Value::Float(number) => number != 0.0,
+            Value::Bool(boole) => boole,
+            Value::Path(path) => !path.as_os_str().is_empty(),
+            Value::Array(values) => values.iter().any(|current| !current.is_empty()),
+            Value::Map(entries) => !entries.is_empty(),
+            Value::Date(_) => true,
+        }
+    }
+
+    /// compares values accordingly their types, a string looking as a number or a boolean is considered as such
+    pub fn compare(&self, other: &VarVal) -> Option<cmp::Ordering> {
+        self.resolved().typed().compare(&other.resolved().typed())
+    }
+
+    pub fn same(&self, other: &VarVal) -> bool {
+        self.compare(other) == Some(cmp::Ordering::Equal)
+    }
+}
+
+impl Value {
+    /// a type of a string value guessed by its content, numbers separated by two or more dots stay a string
+    /// compared as a version
+    pub fn typed(self) -> Value {
+        match self {
+            Value::Str(ref str) => {
+                let trimmed = str.trim();
+                if let Ok(number) = trimmed.parse::<i64>() {
+                    Value::Int(number)
+                } else if let Ok(number) = trimmed.parse::<f64>() && !number.is_nan() && trimmed.chars().any(|c| c.is_ascii_digit()) {
+                    Value::Float(number)
+                } else if trimmed == "true" || trimmed == "false" {
+                    Value::Bool(trimmed == "true")
                 } else {
-                    self.value == "true"
+                    self
                 }
             },
-            VarType::Array => self.values.iter().any(|current| !current.is_empty()),
-            VarType::Number => !self.value.is_empty() && self.value.parse::<i64>().unwrap_or_default() != 0,
-            VarType::Bool => self.value == "true",
-            _ => !self.value.is_empty() // consider adding interpolation
+            value => value
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Str(_) => "string",
+            Value::Int(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::Path(_) => "path",
+            Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Date(_) => "date",
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int(number) => Some(*number as f64),
+            Value::Float(number) => Some(*number),
+            Value::Str(str) => str.trim().parse::<f64>().ok(),
+            _ => None
+        }
+    }
+
+    /// numbers of a version as 1.10.2, a string needs at least two dots to be a version, a not negative
+    /// number can be compared with a version as 1 or 1.2
+    fn version(&self) -> Option<Vec<u64>> {
+        let numbers = |str: &str| str.split('.').map(|part| if !part.is_empty() && part.bytes().all(|c| c.is_ascii_digit()) {
+                part.parse::<u64>().ok()
+            } else {
+                None
+            }).collect();
+        match self {
+            Value::Int(number) => u64::try_from(*number).ok().map(|number| vec![number]),
+            Value::Float(number) => numbers(&number.to_string()),
+            Value::Str(str) if str.matches('.').count() >= 2 => numbers(str.trim()),
+            _ => None
+        }
+    }
+
+    /// an order of typed values, a string comparison is used for values of different types
+    pub fn compare(&self, other: &Value) -> Option<cmp::Ordering> {
+        // versions are compared number by number, a missing number is 0
+        if (matches!(self, Value::Str(_)) || matches!(other, Value::Str(_)))
+            && let (Some(v1), Some(v2)) = (self.version(), other.version()) {
+            let len = v1.len().max(v2.len());
+            let number = |version: &Vec<u64>, pos: usize| version.get(pos).copied().unwrap_or_default();
+            return Some((0..len).map(|pos| number(&v1, pos).cmp(&number(&v2, pos)))
+                .find(|order| *order != cmp::Ordering::Equal).unwrap_or(cmp::Ordering::Equal))
+        }
+        match (self, other) {
+            (Value::Int(v1), Value::Int(v2)) => Some(v1.cmp(v2)),
+            (Value::Int(_) | Value::Float(_), Value::Int(_) | Value::Float(_)) =>
+                self.as_f64()?.partial_cmp(&other.as_f64()?),
+            (Value::Bool(v1), Value::Bool(v2)) => Some(v1.cmp(v2)),
+            (Value::Date(v1), Value::Date(v2)) => Some(v1.cmp(v2)),
+            (Value::Path(v1), Value::Path(v2)) => Some(v1.cmp(v2)),
+            (Value::Array(v1), Value::Array(v2)) => {
+                for (el1, el2) in v1.iter().zip(v2) {
+                    match Value::Str(el1.to_string()).typed().compare(&Value::Str(el2.to_string()).typed()) {
+                        Some(cmp::Ordering::Equal) => continue,
+                        res => return res
+                    }
+                }
+                Some(v1.len().cmp(&v2.len()))
+            },
+            (Value::Map(v1), Value::Map(v2)) => if v1.len() == v2.len() && v1.iter().all(|entry| v2.contains(entry)) {
+                    Some(cmp::Ordering::Equal)
+                } else {
+                    None
+                },
+            _ => Some(self.to_string().cmp(&other.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(str) => write!(f, "{str}"),
+            Value::Int(number) => write!(f, "{number}"),
+            Value::Float(number) => write!(f, "{number}"),
+            Value::Bool(boole) => write!(f, "{boole}"),
+            Value::Path(path) => write!(f, "{}", path.display()),
+            Value::Array(values) => write!(f, "{}", vec_to_str(values)),
+            Value::Map(entries) => write!(f, "{}", entries.iter().map(|(key, value)| format!{"{key}: {value}"}).collect::<Vec<_>>().join(", ")),
+            Value::Date(time) => write!(f, "{}", format_system_time(*time)),
         }
     }
 }
@@ -240,27 +454,42 @@ impl Reader {
         }
         Some(char::from(self.buf[self.pos]))
     }
+
+    fn error(&self, msg: impl Into<String>) -> ScriptError {
+        ScriptError{file: self.file_path.clone(), line: self.line, column: Some(self.line_offset), msg: msg.into()}
+    }
 }
 
 fn open(file_path: &PathBuf) -> io::Result<Reader> {
+    Ok(reader(file_path, Box::new(File::open(file_path)?)))
+}
 
-    Ok(Reader {
-        reader : File::open(file_path)?,
+/// a reader of a script text not saved in the file yet
+fn text_reader(file_path: &Path, text: &str) -> Reader {
+    reader(file_path, Box::new(io::Cursor::new(text.as_bytes().to_vec())))
+}
+
+fn reader(file_path: &Path, reader: Box<dyn Read>) -> Reader {
+    Reader {
+        reader,
         line : 1,
         buf : [0; 256],
         pos : 0,
         end : 0,
         line_offset : 0,
         file_path: file_path.to_string_lossy().to_string(),
-    })
+    }
 }
 
-fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexState, u32) {
+fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> Result<(Lexem, LexState, u32), ScriptError> {
     let mut buffer : [char; MAX_LEX_LEN] = [' '; MAX_LEX_LEN];
     
    // let mut buffer = String::with_capacity(MAX_LEX_LEN);
     let mut buf_fill: usize = 0;
     let mut last_nb = 0;
+    let mut template_depth = 0; // ${ nesting in a parameter, ',' and ')' are a part of a template filter there
+    let mut map_depth = 0; // { nesting in a map value, it is read as an array value till the closing }
+    let mut map_quoted = false; // braces in a quoted key or value of a map aren't counted
     let mut c1 = reader.next();
     let mut prev_state = LexState::Begin;
     let mut prev_buffer : [char; MAX_LEX_LEN] = [' '; MAX_LEX_LEN];
@@ -271,6 +500,11 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
             '"' => {
                 match state {
                     LexState::Begin => state = LexState::QuotedStart,
+                    LexState::InArrayVal if map_depth > 0 => {
+                        map_quoted = !map_quoted;
+                        buffer[buf_fill] = c;
+                        buf_fill += 1;
+                    },
                     LexState::InLex | LexState::InParam | LexState::InArrayVal => {
                         buffer[buf_fill] = c;
                         buf_fill += 1;
@@ -346,7 +580,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1; 
                         state = LexState::InQtParam;
                     }
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             ' ' | '\t' => {
@@ -388,7 +622,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                     },
                     LexState::InType => {
                         state = LexState::Begin;
-                        return (Lexem::Type(buffer[0..buf_fill].iter().collect()), state, reader.line); // TODO add offset
+                        return Ok((Lexem::Type(buffer[0..buf_fill].iter().collect()), state, reader.line)); // TODO add offset
                     },
                     LexState::EndFunction => state = LexState::Begin,
                     LexState::StartValue | LexState::BlockEnd | LexState::IgnoredBlankToEnd | LexState::StartParam => {
@@ -444,7 +678,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InArrayVal
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             '\\' => {
@@ -516,7 +750,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         state = LexState::EscapeEndArray
                     },
                     LexState::End => break,
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             '#' => {
@@ -529,11 +763,11 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                     },
                     LexState::InValue | LexState::StartValue => { // separate in value since # has to be collected toward to comment
                         state = LexState::Comment;
-                        return (Lexem::Value(buffer[0..buf_fill].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Value(buffer[0..buf_fill].iter().collect()), state, reader.line));
                     },
                     LexState::BlankInValue | LexState::InBreak | LexState::BlankOrEnd => { // separate in value since # has to be collected toward to comment
                         state = LexState::Comment;
-                        return (Lexem::Value(buffer[0..last_nb].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Value(buffer[0..last_nb].iter().collect()), state, reader.line));
                     },
                     LexState::InQtValue | LexState::InQtParam | LexState::InQtLex => {
                         buffer[buf_fill] = c;
@@ -555,7 +789,11 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buffer[buf_fill] = '\\';
                         buf_fill += 1;
                         state = LexState::Comment;
-                        return (Lexem::Value(buffer[0..buf_fill].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Value(buffer[0..buf_fill].iter().collect()), state, reader.line));
+                    },
+                    LexState::InArrayVal if map_quoted => {
+                        buffer[buf_fill] = c;
+                        buf_fill += 1;
                     },
                     LexState::InArrayVal | LexState::StartParam | LexState::InParam => {
                         prev_state = state ;
@@ -572,7 +810,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InArrayVal
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             '\n' | '\r' => {
@@ -584,7 +822,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                     LexState::Comment => {
                         state = prev_state; prev_state = LexState::Begin;
                         if state != LexState::InArrayVal {
-                            return (Lexem::Comment(buffer[0..buf_fill].iter().collect()), state, reader.line);
+                            return Ok((Lexem::Comment(buffer[0..buf_fill].iter().collect()), state, reader.line));
                         } else {
                             let comment : String = buffer[0..buf_fill].iter().collect();
                             log.debug(&format!("Commentary: {}, line: {}/{}", comment, reader.line, reader.line_offset));
@@ -597,18 +835,18 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                     },
                     LexState::InValue | LexState::StartValue => {
                         state = LexState::Begin;
-                        return (Lexem::Value(buffer[0..buf_fill].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Value(buffer[0..buf_fill].iter().collect()), state, reader.line));
                     },
                     LexState::BlankInValue => {
                         state = LexState::Begin;
-                        return (Lexem::Value(buffer[0..last_nb].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Value(buffer[0..last_nb].iter().collect()), state, reader.line));
                     },
                     LexState::EndFunction | LexState::BlockEnd => {
                         state = LexState::Begin; 
                     },
                     LexState::InType => {
                         state = LexState::Begin;
-                        return (Lexem::Type(buffer[0..buf_fill].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Type(buffer[0..buf_fill].iter().collect()), state, reader.line));
                     },
                    LexState::InQtParam | LexState::InParamBlank | LexState::InQtValue  | LexState::InArrayVal => {
                         buffer[buf_fill] = c;
@@ -645,7 +883,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                     LexState::EscapeBreakValue | LexState::InBreak => {
                         state = LexState::InBreak;
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             '[' => {
@@ -659,7 +897,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                     LexState::InLex => {
                         state = LexState::RangeStart;
                         //let lexstr: String = buffer[0..buf_fill].iter().collect();
-                        return (Lexem::Variable(buffer[0..buf_fill].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Variable(buffer[0..buf_fill].iter().collect()), state, reader.line));
                     },
                     LexState::Comment | LexState::InValue | LexState::InParam => {
                         buffer[buf_fill] = c;
@@ -715,7 +953,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InArrayVal
                     }
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             ']' => {
@@ -764,29 +1002,49 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buffer[buf_fill] = c;
                         buf_fill += 1;
                     },
+                    LexState::InArrayVal if map_depth > 0 => {
+                        buffer[buf_fill] = c;
+                        buf_fill += 1;
+                    },
                     LexState::InArrayVal => {
                         state = LexState::Begin;
                         buffer[buf_fill] = c;
                         buf_fill += 1; 
                         // probably add type the array value and call process_array_value here first
-                        return (Lexem::Value(buffer[0..buf_fill].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Value(buffer[0..buf_fill].iter().collect()), state, reader.line));
                     },
                     LexState::InType => {
                         // syntax error
-                        log.error(&format!{"Unexpected symbol ']' in type at {}:{}:{}", reader.file_path, reader.line, reader.line_offset});
+                        recovered_error(log, reader.error("Unexpected symbol ']' in type"));
                         state = LexState::UnrecoverableErr;
-                        return (Lexem::EOF, state, reader.line);
+                        return Ok((Lexem::EOF, state, reader.line));
                     },
                     LexState::InParamBlank | LexState::StartParam => {
                         state = LexState::InParam;
                         buffer[buf_fill] = c;
                         buf_fill += 1;
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             '{' => {
                 match state {
+                    LexState::InParam if buf_fill > 0 && buffer[buf_fill - 1] == '$' => {
+                        template_depth += 1;
+                        buffer[buf_fill] = c;
+                        buf_fill += 1;
+                    },
+                    LexState::StartValue => {
+                        state = LexState::InArrayVal;
+                        map_depth = 1;
+                        buffer[buf_fill] = c;
+                        buf_fill += 1;
+                    },
+                    LexState::InArrayVal if map_depth > 0 && !map_quoted => {
+                        map_depth += 1;
+                        buffer[buf_fill] = c;
+                        buf_fill += 1;
+                    },
                     LexState::InValue | LexState::InQtParam | LexState::InQtLex | LexState::InParam |
                        LexState::Comment | LexState::InQtValue | LexState::InArrayVal => {
                         buffer[buf_fill] = c;
@@ -794,11 +1052,11 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                     },
                     LexState::InLex | LexState::BlankOrEnd => {
                         state = LexState::BlockStart;
-                        return (Lexem::BlockHdr(buffer[0..buf_fill].iter().collect()), state, reader.line);
+                        return Ok((Lexem::BlockHdr(buffer[0..buf_fill].iter().collect()), state, reader.line));
                     },
                     LexState::BlockEnd | LexState::Begin => {
                         state = LexState::BlockStart;
-                        return (Lexem::BlockHdr("".to_string()), state, reader.line);
+                        return Ok((Lexem::BlockHdr("".to_string()), state, reader.line));
                     },
                     LexState::InParamBlank | LexState::StartParam => {
                         state = LexState::InParam;
@@ -806,7 +1064,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                     },
                     LexState::BlockStart => {
-                        return (Lexem::BlockHdr("".to_string()), state, reader.line);
+                        return Ok((Lexem::BlockHdr("".to_string()), state, reader.line));
                     },
                     LexState::EscapeQt => {
                         buffer[buf_fill] = '\\';
@@ -848,7 +1106,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InParam
                     }
-                    LexState::InBreak | LexState::StartValue | LexState::BlankInValue => {
+                    LexState::InBreak | LexState::BlankInValue => {
                         buffer[buf_fill] = c;
                         buf_fill += 1; 
                         state = LexState::InValue;
@@ -860,15 +1118,32 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InArrayVal
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             '}' => {
                 match state {
+                    LexState::InParam | LexState::InParamBlank if template_depth > 0 => {
+                        template_depth -= 1;
+                        state = LexState::InParam;
+                        buffer[buf_fill] = c;
+                        buf_fill += 1;
+                    },
+                    LexState::InArrayVal if map_depth > 1 && !map_quoted => {
+                        map_depth -= 1;
+                        buffer[buf_fill] = c;
+                        buf_fill += 1;
+                    },
+                    LexState::InArrayVal if map_depth == 1 && !map_quoted => {
+                        state = LexState::Begin;
+                        buffer[buf_fill] = c;
+                        buf_fill += 1;
+                        return Ok((Lexem::Value(buffer[0..buf_fill].iter().collect()), state, reader.line));
+                    },
                     LexState::Begin | LexState::BlockStart | LexState::BlockEnd => {
                         state = LexState::BlockEnd;
                     
-                        return (Lexem::BlockEnd(None), state, reader.line);
+                        return Ok((Lexem::BlockEnd(None), state, reader.line));
                     },
                     LexState::InParam | LexState::InValue | LexState::InQtParam | LexState::Comment |
                     LexState::InQtValue | LexState::InArrayVal => {
@@ -884,7 +1159,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         state = LexState::BlockEnd;
                     // decide what to do with lex value ????
                         
-                        return (Lexem::BlockEnd(Some(buffer[0..buf_fill].iter().collect())), state, reader.line);
+                        return Ok((Lexem::BlockEnd(Some(buffer[0..buf_fill].iter().collect())), state, reader.line));
                     },
                     LexState::EscapeQtValue => {
                         buffer[buf_fill] = '\\';
@@ -919,7 +1194,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InArrayVal
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             ';' => {
@@ -956,11 +1231,11 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                     }
                     LexState::InValue | LexState::InBreak | LexState::InLex => {
                         state = LexState::Begin;
-                        return (Lexem::Value(buffer[0..buf_fill].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Value(buffer[0..buf_fill].iter().collect()), state, reader.line));
                     },
                     LexState::BlankInValue => {
                         state = LexState::Begin;
-                        return (Lexem::Value(buffer[0..last_nb].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Value(buffer[0..last_nb].iter().collect()), state, reader.line));
                     }
                     LexState::EscapeEndArray => {
                         buffer[buf_fill] = '\\';
@@ -969,7 +1244,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InArrayVal
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             ':' => {
@@ -982,7 +1257,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                     LexState::InValue | LexState::BlankInValue => {
                         state = LexState::InType;
                         last_nb = buf_fill;
-                        return (Lexem::Value(buffer[0..last_nb].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Value(buffer[0..last_nb].iter().collect()), state, reader.line));
                     },
                     LexState::InParam | LexState::InLex | LexState::Comment | LexState::InQtParam |
                     LexState::InQtValue | LexState::InQtLex | LexState::InArrayVal => {
@@ -1025,18 +1300,18 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InArrayVal
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             '=' => {
                 match state {
                     LexState::BlankOrEnd | LexState::IgnoredBlankToEnd => {
                         state = LexState::StartValue; 
-                        return (Lexem::Variable(buffer[0..last_nb].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Variable(buffer[0..last_nb].iter().collect()), state, reader.line));
                     },
                     LexState::InLex => {
                         state = LexState::StartValue; 
-                        return (Lexem::Variable(buffer[0..buf_fill].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Variable(buffer[0..buf_fill].iter().collect()), state, reader.line));
                     },
                     LexState::Comment | LexState::InParam | LexState::InQtParam |
                     LexState::InQtValue | LexState::InQtLex | LexState::InArrayVal |
@@ -1075,18 +1350,18 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InArrayVal
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             '(' => { 
                 match state {
                     LexState::InLex => {
                         state = LexState::StartParam; 
-                        return (Lexem::Function(buffer[0..buf_fill].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Function(buffer[0..buf_fill].iter().collect()), state, reader.line));
                     },
                     LexState::BlankOrEnd => {
                         state = LexState::StartParam; 
-                        return (Lexem::Function(buffer[0..buf_fill].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Function(buffer[0..buf_fill].iter().collect()), state, reader.line));
                     },
                     LexState::InValue | LexState::InParam | LexState::InQtParam | LexState::Comment | 
                     LexState::InQtValue | LexState::InQtLex | LexState::InArrayVal | LexState::InParamBlank => {
@@ -1141,22 +1416,27 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InArrayVal
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             ')' => {
                 match state {
+                    LexState::InParam | LexState::InParamBlank if template_depth > 0 => {
+                        state = LexState::InParam;
+                        buffer[buf_fill] = c;
+                        buf_fill += 1
+                    }
                     LexState::InParam  => {
                         state = LexState::EndFunction; 
-                        return (Lexem::Parameter(buffer[0..buf_fill].iter().collect()), state, reader.line)
+                        return Ok((Lexem::Parameter(buffer[0..buf_fill].iter().collect()), state, reader.line))
                     }
                     LexState::InParamBlank  => {
                         state = LexState::EndFunction; 
-                        return (Lexem::Parameter(buffer[0..last_nb].iter().collect()), state, reader.line)
+                        return Ok((Lexem::Parameter(buffer[0..last_nb].iter().collect()), state, reader.line))
                     }
                     LexState::StartParam => {
                         state = LexState::EndFunction; 
-                        return (Lexem::Parameter(buffer[0..buf_fill].iter().collect()), state, reader.line)
+                        return Ok((Lexem::Parameter(buffer[0..buf_fill].iter().collect()), state, reader.line))
                     }
                     LexState::InValue | LexState::InQtParam | LexState::Comment |
                     LexState::InQtValue | LexState::InQtLex | LexState::InArrayVal 
@@ -1212,7 +1492,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InArrayVal
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             dig @ '0' ..= '9' => {
@@ -1293,22 +1573,27 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InParam
                     }
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             ',' => {
                 match state {
+                    LexState::InParam | LexState::InParamBlank if template_depth > 0 => {
+                        state = LexState::InParam;
+                        buffer[buf_fill] = c;
+                        buf_fill += 1;
+                    },
                     LexState::InParam => {                    
                         state = LexState::StartParam; 
-                        return (Lexem::Parameter(buffer[0..buf_fill].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Parameter(buffer[0..buf_fill].iter().collect()), state, reader.line));
                     },
                     LexState::InParamBlank => {                    
                         state = LexState::StartParam; 
-                        return (Lexem::Parameter(buffer[0..last_nb].iter().collect()), state, reader.line);
+                        return Ok((Lexem::Parameter(buffer[0..last_nb].iter().collect()), state, reader.line));
                     },
                     LexState::StartParam => {
                         state = LexState::StartParam; 
-                        return (Lexem::Parameter("".to_string() /* EMPTY */), state, reader.line);
+                        return Ok((Lexem::Parameter("".to_string() /* EMPTY */), state, reader.line));
                     },
                     LexState::InValue | LexState::InQtParam | LexState::Comment |
                     LexState::InQtValue | LexState::InQtLex | LexState::InArrayVal |
@@ -1371,7 +1656,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InArrayVal
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             },
             '.' => {
@@ -1444,7 +1729,7 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                         buf_fill += 1;
                         state = LexState::InArrayVal
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
 
             },
@@ -1532,10 +1817,10 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
                     },
                     LexState::EndFunction  => {
                         state = LexState::Begin; 
-                        log.error(&format!{"Expected ';' or a new line at  {}:{}:{}", reader.file_path, reader.line, reader.line_offset});
-                        return (Lexem::EOF, state, reader.line)
+                        recovered_error(log, reader.error("Expected ';' or a new line"));
+                        return Ok((Lexem::EOF, state, reader.line))
                     },
-                    _ => todo!("state: {:?} at {}", state, reader.line)
+                    _ => return Err(reader.error(format!("Unexpected symbol {c:?}"))),
                 }
             }
         }
@@ -1543,37 +1828,37 @@ fn read_lex(log: &Log, reader: &mut Reader, mut state: LexState) -> (Lexem, LexS
     }
     match state {
         LexState::InQtLex => {
-            log.error(&format!{"Unexpected ending of the script file in quoted token at  {}:{}:{}", reader.file_path, reader.line, reader.line_offset});
-            return (Lexem::EOF, state, reader.line);
+            recovered_error(log, reader.error("Unexpected ending of the script file in quoted token"));
+            return Ok((Lexem::EOF, state, reader.line));
         },
         LexState::EndFunction | LexState::InParam => {
             //state = 
-            return (Lexem::EOF, state, reader.line);
+            return Ok((Lexem::EOF, state, reader.line));
         },
         LexState::InLex => {
             
         },
         LexState::InValue  => {
             state = LexState::Begin;
-            return (Lexem::Value(buffer[0..buf_fill].iter().collect()), state, reader.line); 
+            return Ok((Lexem::Value(buffer[0..buf_fill].iter().collect()), state, reader.line));
         }, 
         LexState::Begin | LexState::End | LexState::BlockEnd => {
-            return (Lexem::EOF, state, reader.line);
+            return Ok((Lexem::EOF, state, reader.line));
         },
         LexState::InType => {
             state = LexState::End;
-            return (Lexem::Type(buffer[0..buf_fill].iter().collect()), state, reader.line);
+            return Ok((Lexem::Type(buffer[0..buf_fill].iter().collect()), state, reader.line));
         },
         LexState::Comment => {
             state = LexState::End;
-            return (Lexem::Comment(buffer[0..buf_fill].iter().collect()), state, reader.line);
+            return Ok((Lexem::Comment(buffer[0..buf_fill].iter().collect()), state, reader.line));
         },
-        _ => todo!("state: {:?} at {}", state, reader.line)
+        _ => return Err(reader.error("Unexpected ending of the script file")),
     }
-    (Lexem::Variable(buffer[0..buf_fill].iter().collect()), state, reader.line)
+    Ok((Lexem::Variable(buffer[0..buf_fill].iter().collect()), state, reader.line))
 }
 
-fn process_lex_header(_log: &Log, value : &str, _vars: &HashMap<String, VarVal>) -> Box<(String, String, String, String)> {
+pub fn process_lex_header(_log: &Log, value : &str, _vars: &HashMap<String, VarVal>) -> Result<Box<(String, String, String, String)>, String> {
     let mut buf = Vec::with_capacity(4096);
 
     let chars = value.chars();
@@ -1653,7 +1938,7 @@ fn process_lex_header(_log: &Log, value : &str, _vars: &HashMap<String, VarVal>)
                     HdrState::InNameQt | HdrState::InPathQt | HdrState::InWorkQt => {
                         buf.push(c)
                     },
-                    _ => todo!("state: {:?}", state)
+                    _ => return Err(format!("Unexpected symbol {c:?} in the header {value}"))
                 }
 
             },
@@ -1683,7 +1968,7 @@ fn process_lex_header(_log: &Log, value : &str, _vars: &HashMap<String, VarVal>)
                     HdrState::InName => {
                         state = HdrState::InNameQt;
                     }
-                    _ => todo!("header state: {:?}", state)
+                    _ => return Err(format!("Unexpected symbol {c:?} in the header {value}"))
                 }
             },
             _ => {
@@ -1745,141 +2030,386 @@ fn process_lex_header(_log: &Log, value : &str, _vars: &HashMap<String, VarVal>)
             path = buf[0..last_blank].iter().collect();
         },
         HdrState::NameStart | HdrState::WorkDiv | HdrState::PathDiv=> (),
-        _ => todo!("state: {:?}", state)
+        _ => return Err(format!("Unclosed quote in the header {value}"))
     }
     //println!{"=>{lex_type} {name} '{work_dir}' '{path}'"};
-    Box::new((lex_type.to_string(), name.to_string(), work_dir.to_string(), path.to_string()))
+    Ok(Box::new((lex_type.to_string(), name.to_string(), work_dir.to_string(), path.to_string())))
 }
 
 pub fn process_template_value(log: &Log, value : &str, vars: &GenBlock, res_prev: &Option<VarVal>) -> Box<String> {
     // String interpolation
-    let mut buf = Vec::with_capacity(4096);
-    let mut buf_var = Vec::with_capacity(256); // buf for var name
-    let chars = value.chars();
+    let mut expansion = Expansion::default();
+    let res = expand_template(log, value, vars, res_prev, &mut expansion);
+    if let Some(msg) = expansion.failure {
+        // it fails the target, so it's reported with the target failure
+        crate::fun::report_failure(format!("{msg} at {}:{}: ", vars.script_path(), vars.script_line))
+    }
+    Box::new(res)
+}
+
+/// expands the value as process_template_value, but returns a message of ${name:?message} as an error,
+/// it's used when no target is executed
+pub fn try_template_value(log: &Log, value : &str, vars: &GenBlock, res_prev: &Option<VarVal>) -> Result<String, String> {
+    let mut expansion = Expansion::default();
+    let res = expand_template(log, value, vars, res_prev, &mut expansion);
+    match expansion.failure {
+        Some(msg) => Err(msg),
+        None => Ok(res)
+    }
+}
+
+/// names of the variables in expanding are used for a detection of a cycle, failure keeps
+/// a message of the first problem failing the expansion, as a required variable which isn't set
+#[derive(Default)]
+struct Expansion {
+    expanding: Vec<String>,
+    failure: Option<String>,
+}
+
+/// expands variables of the value, a value of a variable is expanded too
+fn expand_template(log: &Log, value : &str, vars: &GenBlock, res_prev: &Option<VarVal>, expansion: &mut Expansion) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let mut buf = String::with_capacity(value.len());
     let mut state = TemplateState::InVal;
-    let mut was_replacement = false;
-    for c in chars {
-        match c {
-            '$' => {
-                match state {
-                    TemplateState::InVal  => state = TemplateState::VarStart,
-                    TemplateState::VarStart => {
-                        buf.push(c);
-                    },
-                    TemplateState::InVar => {buf_var.push(c)},
-                    _ => todo!()
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        pos += 1;
+        match state {
+            TemplateState::InVal => {
+                if c == '$' {
+                    state = TemplateState::VarStart
+                } else {
+                    buf.push(c)
                 }
             },
-            '{' => {
-                match state {
-                    TemplateState::VarStart => state = TemplateState::InVar,
-                    TemplateState::InVal  => {
-                        buf.push(c);
+            TemplateState::VarStart => {
+                match c {
+                    // $${ is an escape of ${
+                    '$' if chars.get(pos) == Some(&'{') => {
+                        buf.push_str("${");
+                        pos += 1;
+                        state = TemplateState::InVal
                     },
-                    TemplateState::InVar => buf_var.push(c),
-                    _ => todo!()
-                }
-            },
-            '}' => {
-                match state {
-                    TemplateState::VarStart => {
-                        state = TemplateState::InVal;
+                    '$' => buf.push(c),
+                    '{' => state = TemplateState::InVar,
+                    _ => {
                         buf.push('$');
                         buf.push(c);
-                    },
-                    TemplateState::InVal  => {
-                        buf.push(c);
-                    },
-                    TemplateState::InVar => {
-                        state = TemplateState::InVal;
-                        let var : String = buf_var.clone().into_iter().collect();
-                       // println!("looking {:?}", buf_var);
-                        // check name for ~~ and then use global thread local
-                        let res = if var == PREV_VAL {
-                            res_prev
-                        } else {&vars.search_up( &var)};
-                        match res {
-                            Some(var) => {
-                               // println!("found {:?}", var);
-                               // TODO avoid replacement in an infinitive loop
-                               match var.val_type {
-                                    VarType::Environment  => {
-                                      //  println!("looking for {} in env", var.value);
-                                        match env::var(&var.value) {
-                                             Ok(val) => {
-                                                 for vc in val.chars() {
-                                                     buf.push(vc);
-                                                 }
-                                             },
-                                             Err(_e) => {
-                                                 for vc in var.value.chars() {
-                                                     buf.push(vc);
-                                                 } 
-                                             },
-                                        };
-                                    },
-                                    VarType::Array => {
-                                        let chars = vec_to_str(&var.values);
-                                        for vc in chars.chars() {
-                                            buf.push(vc);
-                                        }
-                                    },
-                                    VarType::Property => {
-                                        if let Some(val) = get_property(&var.value) {
-                                            for vc in val.chars() {
-                                                buf.push(vc);
-                                            }
-                                        } else {
-                                            for vc in var.value.chars() {
-                                                buf.push(vc);
-                                            }
-                                        }
-                                    },
-                                    _ => {
-                                        for vc in var.value.chars() {
-                                            buf.push(vc);
-                                        }
-                                    }
-                               }
-                               was_replacement = true;
-                            },
-                            None => {
-                               // println!("restoring {:?}", buf_var);
-                                buf.push('$');
-                                buf.push('{');
-                                buf.append(&mut buf_var);
-                                buf.push('}');
+                        state = TemplateState::InVal
+                    }
+                }
+            },
+            TemplateState::InVar => {
+                // a default or an alternative value can have variables, so braces are counted
+                let start = pos - 1;
+                let mut depth = 1;
+                let mut end = start;
+                while end < chars.len() {
+                    match chars[end] {
+                        '{' => depth += 1,
+                        '}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break
                             }
+                        },
+                        _ => ()
+                    }
+                    end += 1
+                }
+                let expr: String = chars[start..end.min(chars.len())].iter().collect();
+                if end >= chars.len() {
+                    // no closing brace
+                    buf.push_str("${");
+                    buf.push_str(&expr);
+                    break
+                }
+                pos = end + 1;
+                state = TemplateState::InVal;
+                buf.push_str(&expand_var(log, &expr, vars, res_prev, expansion))
+            }
+        }
+    }
+    if state == TemplateState::VarStart {
+        buf.push('$')
+    }
+    buf
+}
+
+/// a parsed variable of a template, as name[index]|filter(args)|filter:-default
+struct VarExpr<'a> {
+    name: &'a str,
+    index: Option<&'a str>,
+    filters: Vec<(&'a str, Option<&'a str>)>,
+    op: &'a str,
+    arg: &'a str,
+}
+
+fn parse_var_expr(expr: &str) -> VarExpr<'_> {
+    let is_op = |rest: &str| [":-", ":?", ":+"].iter().any(|op| rest.starts_with(op));
+    // an end of a name or a filter
+    let part_end = |from: usize| {
+        expr[from..].char_indices()
+            .find(|(at, c)| matches!(c, '[' | '|' | '(') || is_op(&expr[from + at..]))
+            .map_or(expr.len(), |(at, _)| from + at)
+    };
+    let mut pos = part_end(0);
+    let name = &expr[..pos];
+    let mut index = None;
+    if expr[pos..].starts_with('[')
+        && let Some(len) = expr[pos..].find(']')
+    {
+        index = Some(&expr[pos + 1..pos + len]);
+        pos += len + 1
+    }
+    let mut filters = Vec::new();
+    while expr[pos..].starts_with('|') {
+        let end = part_end(pos + 1);
+        let filter = expr[pos + 1..end].trim();
+        pos = end;
+        let mut args = None;
+        if expr[pos..].starts_with('(') {
+            // arguments can have parentheses inside
+            let mut depth = 0;
+            for (at, c) in expr[pos..].char_indices() {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => ()
+                }
+                if depth == 0 {
+                    args = Some(&expr[pos + 1..pos + at]);
+                    pos += at + 1;
+                    break
+                }
+            }
+            if args.is_none() {
+                args = Some(&expr[pos + 1..]);
+                pos = expr.len()
+            }
+        }
+        filters.push((filter, args))
+    }
+    let (op, arg) = if is_op(&expr[pos..]) {
+        (&expr[pos..pos + 2], &expr[pos + 2..])
+    } else {
+        ("", "")
+    };
+    VarExpr { name, index, filters, op, arg }
+}
+
+/// expands name, name:-default, name:?message, or name:+alternative, the name can be followed by
+/// [index] of an array element, and by filters as |upper|replace(a,b)
+fn expand_var(log: &Log, expr: &str, vars: &GenBlock, res_prev: &Option<VarVal>, expansion: &mut Expansion) -> String {
+    let VarExpr { name, index, filters, op, arg } = parse_var_expr(expr);
+    let location = || format!("{}:{}", vars.script_path(), vars.script_line);
+    if expansion.expanding.iter().any(|expanded| expanded == name) {
+        let mut cycle = expansion.expanding.clone();
+        cycle.push(name.to_string());
+        expansion.failure.get_or_insert(format!("Variables {} make a cycle", cycle.join(" -> ")));
+        return format!("${{{expr}}}")
+    }
+    let var = if name == PREV_VAL {
+        res_prev.clone()
+    } else {
+        vars.search_up(name)
+    };
+    // a key of a map as map.key, when there is no variable of the name
+    let var = var.or_else(|| {
+        let (map, key) = name.split_once('.')?;
+        vars.search_up(map)?.entry(key).map(VarVal::from_string)
+    });
+    // a key of a map can be an index too
+    let (var, index) = match (var, index) {
+        (Some(var), Some(key)) if var.is_map() => {
+            let key = expand_template(log, key, vars, res_prev, expansion);
+            match var.entry(key.trim()) {
+                Some(value) => (Some(VarVal::from_string(value)), None),
+                None => {
+                    expansion.failure.get_or_insert(format!("Key {key} isn't found in {name}"));
+                    (Some(VarVal::from_string("")), None)
+                }
+            }
+        }
+        other => other
+    };
+    let values = var.map(|var| match var.resolved() {
+        Value::Array(values) => values,
+        value => vec![value.to_string()]
+    });
+    let is_array = values.as_ref().is_some_and(|values| values.len() != 1);
+    let values = values.map(|values| {
+        expansion.expanding.push(name.to_string());
+        let values: Vec<String> = values.iter().map(|value| expand_template(log, value, vars, res_prev, expansion)).collect();
+        expansion.expanding.pop();
+        values
+    });
+    let value = values.map(|mut values| {
+        if let Some(index) = index {
+            let index = expand_template(log, index, vars, res_prev, expansion);
+            match index.trim().parse::<usize>().ok().filter(|index| *index < values.len()) {
+                Some(index) => values = vec![values.swap_remove(index)],
+                None => {
+                    expansion.failure.get_or_insert(format!("Index {index} is out of bounds of {name}"));
+                    values = Vec::new()
+                }
+            }
+        }
+        let mut is_array = is_array && index.is_none();
+        for (filter, args) in &filters {
+            let args = args.map(|args| expand_template(log, args, vars, res_prev, expansion));
+            match *filter {
+                // join without arguments uses a blank as the separator
+                "join" => {
+                    values = vec![values.join(args.as_deref().unwrap_or(" "))];
+                    is_array = false;
+                    continue
+                }
+                "replace" => {
+                    match args.as_deref().and_then(|args| args.split_once(',')) {
+                        Some((from, to)) if !from.is_empty() => {
+                            values.iter_mut().for_each(|value| *value = value.replace(from, to))
                         }
-                        buf_var.clear();
-                    },
-                    _ => todo!()
+                        _ => {
+                            expansion.failure.get_or_insert(format!("Filter replace of {name} requires 2 arguments"));
+                        }
+                    }
+                    continue
+                }
+                "upper" | "lower" | "trim" | "basename" | "dirname" | "ext" => (),
+                _ => {
+                    expansion.failure.get_or_insert(format!("Unknown filter {filter} of {name}"));
+                    continue
                 }
+            }
+            for value in values.iter_mut() {
+                *value = match *filter {
+                    "upper" => value.to_uppercase(),
+                    "lower" => value.to_lowercase(),
+                    "trim" => value.trim().to_string(),
+                    "basename" => Path::new(value).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+                    "dirname" => Path::new(value).parent().map(|dir| dir.display().to_string()).unwrap_or_default(),
+                    _ => Path::new(value).extension().map(|ext| ext.to_string_lossy().to_string()).unwrap_or_default(),
+                }
+            }
+        }
+        if is_array {
+            vec_to_str(&values)
+        } else {
+            values.concat()
+        }
+    });
+    let is_set = value.as_ref().is_some_and(|value| !value.is_empty());
+    match op {
+        ":-" if !is_set => expand_template(log, arg, vars, res_prev, expansion),
+        ":?" if !is_set => {
+            let msg = if arg.is_empty() {
+                format!("Variable {name} isn't set")
+            } else {
+                expand_template(log, arg, vars, res_prev, expansion)
+            };
+            expansion.failure.get_or_insert(msg);
+            String::new()
+        },
+        ":+" if is_set => expand_template(log, arg, vars, res_prev, expansion),
+        ":+" => String::new(),
+        _ => match value {
+            Some(value) => value,
+            // an unknown variable is kept as is, but it's likely a mistake
+            None if op.is_empty() => {
+                log.warning(&format!("Variable {name} isn't found and kept as is at {}: ", location()));
+                format!("${{{expr}}}")
+            }
+            None => String::new(),
+        }
+    }
+}
+
+/// parses a map value in the form {key: value, ...}, a key or a value can be quoted
+fn process_map_value(value: &str) -> Result<Vec<(String, String)>, String> {
+    let Some(inner) = value.strip_prefix('{').and_then(|value| value.strip_suffix('}')) else {
+        return Err("a map isn't enclosed in {}".to_string())
+    };
+    // adds a completed entry, a later value of the same key replaces a former one
+    fn add_entry(res: &mut Vec<(String, String)>, key: Option<String>, mut buf: String, kept: usize) -> Result<(), String> {
+        buf.truncate(kept);
+        match key {
+            Some(key) => match res.iter_mut().find(|(name, _)| *name == key) {
+                Some(entry) => entry.1 = buf,
+                None => res.push((key, buf))
             },
-            _ => {
-                match state {
-                    TemplateState::InVal => {
-                        buf.push(c);
-                    },
-                    TemplateState::InVar => buf_var.push(c),
-                    TemplateState::VarStart => {
-                        buf.push('$');
-                        buf.push(c);
-                        state = TemplateState::InVal;
-                    },
-                    _ => todo!()
+            None if buf.is_empty() => (), // a trailing comma
+            None => return Err(format!("no key of the value {buf}"))
+        }
+        Ok(())
+    }
+    let mut res = Vec::new();
+    let mut key: Option<String> = None;
+    let mut buf = String::new();
+    let mut kept = 0; // the length without trailing blanks
+    let mut quoted = false;
+    let mut escape = false;
+    let mut depth = 0; // ${ nesting
+    let mut prev = ' ';
+    for c in inner.chars() {
+        if escape {
+            if !matches!(c, '"' | ',' | ':' | '{' | '}' | '\\') {
+                buf.push('\\')
+            }
+            buf.push(c);
+            kept = buf.len();
+            escape = false
+        } else if c == '\\' {
+            escape = true
+        } else if quoted {
+            if c == '"' {
+                quoted = false
+            } else {
+                buf.push(c)
+            }
+            kept = buf.len()
+        } else {
+            match c {
+                '"' => quoted = true,
+                '{' if prev == '$' => {
+                    depth += 1;
+                    buf.push(c);
+                    kept = buf.len()
+                },
+                '}' if depth > 0 => {
+                    depth -= 1;
+                    buf.push(c);
+                    kept = buf.len()
+                },
+                ':' if depth == 0 && key.is_none() => {
+                    buf.truncate(kept);
+                    if buf.is_empty() {
+                        return Err("an empty key".to_string())
+                    }
+                    key = Some(std::mem::take(&mut buf));
+                    kept = 0
+                },
+                ',' if depth == 0 => {
+                    add_entry(&mut res, key.take(), std::mem::take(&mut buf), kept)?;
+                    kept = 0
+                },
+                c if c.is_whitespace() && buf.is_empty() => (),
+                c => {
+                    buf.push(c);
+                    if !c.is_whitespace() {
+                        kept = buf.len()
+                    }
                 }
             }
         }
+        prev = c
     }
-    // temporay hack (no loop detection )
-    let expanded_val:String = buf.into_iter().collect();
-    if was_replacement {
-        log.debug(&format!{"expanding {}", expanded_val});
-        process_template_value(log, &expanded_val, vars, res_prev)
-    } else {
-        Box::new(expanded_val)
+    if quoted {
+        return Err("a quote isn't closed".to_string())
     }
+    add_entry(&mut res, key, buf, kept)?;
+    Ok(res)
 }
 
 fn process_array_value(_log: &Log, value : &str) -> Result<Vec<String>, String> {
@@ -1901,7 +2431,7 @@ fn process_array_value(_log: &Log, value : &str) -> Result<Vec<String>, String>
                         buf[pos] = c;
                         pos += 1;
                     },
-                    _ => todo!("state: {:?} at {array_line}:{array_pos}", state)
+                    _ => return Err(format!("unexpected symbol {c:?} at {array_line}:{array_pos}"))
                 }
             },
             ']' => {
@@ -1926,7 +2456,7 @@ fn process_array_value(_log: &Log, value : &str) -> Result<Vec<String>, String>
                     LexState::RangeStart => {
                         return Ok(res)
                     },
-                    _ => todo!("state: {:?} at {array_line}:{array_pos}", state)
+                    _ => return Err(format!("unexpected symbol {c:?} at {array_line}:{array_pos}"))
                 }
             },
             '"' => {
@@ -1951,7 +2481,7 @@ fn process_array_value(_log: &Log, value : &str) -> Result<Vec<String>, String>
                         pos += 1;
                         state = LexState::InQtParam;
                     },
-                    _ => todo!("state: {:?} at {array_line}:{array_pos}", state)
+                    _ => return Err(format!("unexpected symbol {c:?} at {array_line}:{array_pos}"))
                 }
             },
             '\\' => {
@@ -1965,7 +2495,7 @@ fn process_array_value(_log: &Log, value : &str) -> Result<Vec<String>, String>
                     LexState::InQtParam  => {
                         state = LexState::EscapeParam;
                     },
-                    _ => todo!("state: {:?} at {array_line}:{array_pos}", state)
+                    _ => return Err(format!("unexpected symbol {c:?} at {array_line}:{array_pos}"))
                 }
             },
             ',' => {
@@ -1994,7 +2524,7 @@ fn process_array_value(_log: &Log, value : &str) -> Result<Vec<String>, String>
                     LexState::EndQtParam => {
                         state = LexState:: StartParam;
                     }, 
-                    _ => todo!("state: {:?} at {array_line}:{array_pos}", state)
+                    _ => return Err(format!("unexpected symbol {c:?} at {array_line}:{array_pos}"))
                 }
             },
             ' ' | '\t' | '\n' | '\r' => {
@@ -2021,7 +2551,7 @@ fn process_array_value(_log: &Log, value : &str) -> Result<Vec<String>, String>
                     LexState::RangeStart | LexState:: StartParam => {
 
                     },
-                    _ => todo!("state: {:?} at {array_line}:{array_pos}", state)
+                    _ => return Err(format!("unexpected symbol {c:?} at {array_line}:{array_pos}"))
                 }
             },
             //':' =>
@@ -2047,7 +2577,7 @@ fn process_array_value(_log: &Log, value : &str) -> Result<Vec<String>, String>
                         buf[pos] = c;
                         pos += 1;
                     },
-                    _ => todo!("state: {:?} at {array_line}:{array_pos}", state)
+                    _ => return Err(format!("unexpected symbol {c:?} at {array_line}:{array_pos}"))
                 }
             }
         }
@@ -2055,9 +2585,65 @@ fn process_array_value(_log: &Log, value : &str) -> Result<Vec<String>, String>
     Err(value.to_string())
 }
 
+fn recovered_error(log: &Log, err: ScriptError) {
+    log.error(&err.to_string());
+    RECOVERED_ERRORS.write().unwrap().push(err)
+}
+
+pub fn recovered_errors() -> usize {
+    RECOVERED_ERRORS.read().unwrap().len()
+}
+
+/// errors reported after the given number of them
+pub fn recovered_errors_since(from: usize) -> Vec<ScriptError> {
+    RECOVERED_ERRORS.read().unwrap().iter().skip(from).cloned().collect()
+}
+
+/// an error in an include script is reported as is, otherwise the include location is reported
+fn include_error(reader: &Reader, include_path: &Path, e: Box<dyn Error>) -> Box<dyn Error> {
+    if e.is::<ScriptError>() {
+        e
+    } else {
+        Box::new(reader.error(format!("Can't process an include script {include_path:?}, problem: {e}")))
+    }
+}
+
+/// a lexem with a line where it was read, used for formatting a script
+pub struct LineLexem {
+    pub lexem: Lexem,
+    pub line: u32,
+    pub end_function: bool, // the parameter closes a function call
+}
+
+/// reads all lexems of a script text without processing them
+pub fn read_lexems(log: &Log, file: &Path, text: &str) -> Result<Vec<LineLexem>, ScriptError> {
+    let mut all_chars = text_reader(file, text);
+    let mut res = Vec::new();
+    let mut state = LexState::Begin;
+    while state != LexState::End {
+        let (lexem, state2, line) = read_lex(log, &mut all_chars, state)?;
+        if lexem == Lexem::EOF {
+            break
+        }
+        res.push(LineLexem{end_function: state2 == LexState::EndFunction, lexem, line});
+        state = state2;
+    }
+    Ok(res)
+}
+
 pub fn process(log: &Log, file: & PathBuf, block: GenBlockTup) -> Result<(), Box<dyn Error>> {
+    let all_chars = open(file)?;
+    process_reader(log, file, all_chars, block)
+}
+
+/// processes a script text of the file, an editor can have it not saved yet
+pub fn process_text(log: &Log, file: &Path, text: &str, block: GenBlockTup) -> Result<(), Box<dyn Error>> {
+    process_reader(log, file, text_reader(file, text), block)
+}
+
+fn process_reader(log: &Log, file: &Path, mut all_chars: Reader, block: GenBlockTup) -> Result<(), Box<dyn Error>> {
     let current_script_path = block.add_var(String::from("~script_path~"), VarVal::from_string(file.parent().unwrap().display().to_string()));
-    let mut all_chars = open(file)?;
+    crate::fun::watch_file(&file.display().to_string());
     
     //let mut func_stack = Vec::new();
     //let mut block_stack : Vec<&mut GenBlock> = Vec::new();
@@ -2065,10 +2651,13 @@ pub fn process(log: &Log, file: & PathBuf, block: GenBlockTup) -> Result<(), Box
     // current block
     let mut scoped_block = block; 
     let mut current_name = "".to_string();
+    // a function definition or a 'for' over a function result, waiting for its body
+    let mut pending_body: Option<GenBlockTup> = None;
     while state != LexState::End {
         // consider returning a partial lexem for example, interrupted by a comment
-        let ( lex, mut state2, line) = read_lex(log, &mut all_chars, state);
+        let ( lex, mut state2, line) = read_lex(log, &mut all_chars, state)?;
         log.debug(&format!("Lex: {:?}, line: {}/{}, state: {:?}", lex, all_chars.line, all_chars.line_offset, state2));
+        let mut body_owner = pending_body.take();
         match lex {
             Lexem::EOF => {
                 state2 = LexState::End;
@@ -2077,16 +2666,24 @@ pub fn process(log: &Log, file: & PathBuf, block: GenBlockTup) -> Result<(), Box
                 current_name = name.to_string();
             },
             Lexem::Value(value) => {
-               // consider it can be an array in form [v1,v2,...vn]
+               // consider it can be an array in form [v1,v2,...vn], or a map in form {k1: v1, ...kn: vn}
                let c_b = 
                 if value.starts_with("[") && value.ends_with("]") {
                     let res = process_array_value(log, &value);
                     if let Ok(res) = res {
                         VarVal::from_vec(res)
                     } else {
-                        log.error(&format!{"The array isn't well defined: {} at  {}:{}:{}", value, file.to_string_lossy(), all_chars.line, all_chars.line_offset});
+                        recovered_error(log, all_chars.error(format!{"The array isn't well defined: {}", value}));
                         VarVal::from_string(&value)
                     }
+                } else if value.starts_with("{") && value.ends_with("}") {
+                    match process_map_value(&value) {
+                        Ok(res) => VarVal::from_map(res),
+                        Err(err) => {
+                            recovered_error(log, all_chars.error(format!{"The map isn't well defined: {}, {}", value, err}));
+                            VarVal::from_string(&value)
+                        }
+                    }
                 } else {VarVal::from_string(&value)}
                 ;
                 if current_name.is_empty() {
@@ -2099,7 +2696,7 @@ pub fn process(log: &Log, file: & PathBuf, block: GenBlockTup) -> Result<(), Box
             },
             Lexem::Function(name) => {
                 // name can be function + main argument
-                let (type_hdr,name,work,path) = *process_lex_header(log, &name, &scoped_block.0.as_ref().borrow_mut().vars) ;
+                let (type_hdr,name,work,path) = *process_lex_header(log, &name, &scoped_block.0.as_ref().borrow_mut().vars).map_err(|msg| all_chars.error(msg))?;
                 let mut func = GenBlock::new(BlockType::Function);
                 //fun::GenBlockTup(Rc::new(RefCell::new(GenBlock::new(BlockType::Function))));
                 func.name = Some(type_hdr);
@@ -2118,32 +2715,32 @@ pub fn process(log: &Log, file: & PathBuf, block: GenBlockTup) -> Result<(), Box
                     Some(var) => { 
                         match var_type.as_str() {
                             "file" => {
-                                let c_b = VarVal{val_type:VarType::File, value:var.value.clone(), values: Vec::new()};
+                                let c_b = var.with_type(VarType::File);
                                 bl.vars.insert(current_name.to_string(), c_b);
                             },
                             "prop" => {
                                 //  println!("prop {} in {:?}", var.value, bl.block_type);
-                                  let c_b = VarVal{val_type:VarType::Property, value:var.value.clone(), values: Vec::new()};
+                                  let c_b = var.with_type(VarType::Property);
                                   bl.vars.insert(current_name.to_string(), c_b);
                               },
                             "env" => {
                               //  println!("env {} in {:?}", var.value, bl.block_type);
-                                let c_b = VarVal{val_type:VarType::Environment, value:var.value.clone(), values: Vec::new()};
+                                let c_b = var.with_type(VarType::Environment);
                                 bl.vars.insert(current_name.to_string(), c_b);
                             },
                             "rep-rust" | "rep-crate"=> {
                                 //let at_pos = 
                                 //  println!("env {} in {:?}", var.value, bl.block_type);
-                                  let c_b = VarVal{val_type:VarType::RepositoryRust, value:var.value.clone(), values: Vec::new()};
+                                  let c_b = var.with_type(VarType::RepositoryRust);
                                   bl.vars.insert(current_name.to_string(), c_b);
                               },
                             "rep-maven" => {
                                 //let at_pos = 
                                 //  println!("env {} in {:?}", var.value, bl.block_type);
-                                  let c_b = VarVal{val_type:VarType::RepositoryMaven, value:var.value.clone(), values: Vec::new()};
+                                  let c_b = var.with_type(VarType::RepositoryMaven);
                                   bl.vars.insert(current_name.to_string(), c_b);
                               },
-                            _ => log.error(&format!("Unknown type '{}' ignored at  {}:{}:{}", var_type, bl.script_path(), all_chars.line, all_chars.line_offset))
+                            _ => recovered_error(log, all_chars.error(format!("Unknown type '{}' ignored", var_type)))
                         }
                         
                     },
@@ -2171,56 +2768,77 @@ pub fn process(log: &Log, file: & PathBuf, block: GenBlockTup) -> Result<(), Box
                                       // println!("found {:?}", var);
                                         match var.val_type {
                                             VarType::File => {
-                                                let var_val = *process_template_value(log, &var.value, &scoped_block.0.as_ref().borrow_mut(), &None);
+                                                let var_val = try_template_value(log, &var.text(), &scoped_block.0.as_ref().borrow_mut(), &None)
+                                                    .map_err(|msg| all_chars.error(msg))?;
                                                 let parent_scoped_block = scoped_block.parent();
                                                 if let Some(block) = parent_scoped_block {
                                                     let mut include_path = PathBuf::from(var_val);
                                                     if !include_path.has_root() {
                                                         match scoped_block.search_up(&String::from("~script_path~")) {
-                                                            Some(var) => include_path = PathBuf::from(var.value).join(include_path),
+                                                            Some(var) => include_path = PathBuf::from(var.text()).join(include_path),
                                                             _ => {
                                                                 let cwd = scoped_block.search_up(crate::CWD);
                                                                 if let Some(cwd) = cwd {
-                                                                    include_path = PathBuf::from(cwd.value).join(include_path)
+                                                                    include_path = PathBuf::from(cwd.text()).join(include_path)
                                                                 }
                                                             }
                                                         }
                                                     }
                                                     if let Err(e) = process(log, &include_path, block.clone()) {
-                                                        log.error(&format!("Can't process an include script {include_path:?} at {0}:{1}: , problem: {e}", scoped_block.0.borrow().script_path(), all_chars.line));
-                                                        return Err(e)
+                                                        return Err(include_error(&all_chars, &include_path, e))
                                                     }
                                                 }
                                             },
-                                            _ => log.error(&format!("The include location variable {} isn't type file , the include is ignored at  {}:{}: ", value, scoped_block.0.borrow().script_path(), all_chars.line)),
+                                            _ => recovered_error(log, all_chars.error(format!("The include location variable {} isn't type file , the include is ignored", value))),
                                         }
                                     },
                                     None => {
-                                        let temp_expand = *process_template_value(log, &value, &scoped_block.0.as_ref().borrow_mut(), &None);
+                                        let temp_expand = try_template_value(log, &value, &scoped_block.0.as_ref().borrow_mut(), &None)
+                                            .map_err(|msg| all_chars.error(msg))?;
                                         log.debug(&format!{"Expand the include template {}", temp_expand});
                                         let parent_scoped_block = scoped_block.parent();
                                         if let Some(block) = parent_scoped_block {
                                             let mut include_path = PathBuf::from(temp_expand);
                                             if !include_path.has_root() {
                                                 match scoped_block.search_up(&String::from("~script_path~")) {
-                                                    Some(var) => include_path = PathBuf::from(var.value).join(include_path),
+                                                    Some(var) => include_path = PathBuf::from(var.text()).join(include_path),
                                                     _ => {
                                                         let cwd = scoped_block.search_up(crate::CWD);
                                                         if let Some(cwd) = cwd {
-                                                            include_path = PathBuf::from(cwd.value).join(include_path)
+                                                            include_path = PathBuf::from(cwd.text()).join(include_path)
                                                         }
                                                     }
                                                 }
                                             }
                                             if let Err(e) = 
                                                 process(log, &include_path, block.clone()) {
-                                                    log.error(&format!("Can't process an include script {include_path:?} at {}:{}, problem: {}", scoped_block.0.borrow().script_path(), all_chars.line, e));
-                                                    return Err(e)
+                                                    return Err(include_error(&all_chars, &include_path, e))
                                                 }
                                         }
                                     }
                                 }
                             },
+                            "fun" => {
+                                let mut fun_def = scoped_block.borrow_mut();
+                                fun_def.block_type = BlockType::Fun;
+                                fun_def.name = fun_def.flex.take();
+                                if fun_def.name.is_none() {
+                                    recovered_error(log, fun_def.script_error("A function name isn't specified"))
+                                }
+                                drop(fun_def);
+                                pending_body = Some(scoped_block.clone())
+                            },
+                            "for" => {
+                                // for var:range(...) {, the function name is kept as the range
+                                let mut for_block = scoped_block.borrow_mut();
+                                for_block.block_type = BlockType::For;
+                                for_block.name = for_block.flex.take();
+                                if for_block.name.is_none() {
+                                    recovered_error(log, for_block.script_error("A 'for' variable isn't specified"))
+                                }
+                                drop(for_block);
+                                pending_body = Some(scoped_block.clone())
+                            },
                             _ => ()
                         }
                     } 
@@ -2239,7 +2857,7 @@ pub fn process(log: &Log, file: & PathBuf, block: GenBlockTup) -> Result<(), Box
                        BlockType::Main
                    };*/
                 current_name.clear();
-                let (type_hdr,name,work,path) = *process_lex_header(log, &value, &scoped_block.0.as_ref().borrow_mut().vars) ;
+                let (type_hdr,name,work,path) = *process_lex_header(log, &value, &scoped_block.0.as_ref().borrow_mut().vars).map_err(|msg| all_chars.error(msg))?;
                 log.debug(&format!("Type: {}, name: {}, work dir: '{}', path; '{}'", type_hdr,name,work,path));
                 match type_hdr.as_str() {
                     "target" => {
@@ -2251,10 +2869,15 @@ pub fn process(log: &Log, file: & PathBuf, block: GenBlockTup) -> Result<(), Box
                             inner_block.dir = Some(work);
                             inner_block.flex = Some(path);
                             inner_block.script_line = all_chars.line;
+                            inner_block.vars.insert(String::from(crate::TARGET_SCRIPT), VarVal::from_string(&all_chars.file_path));
                             //println!{"name {:?} dir {:?} flex {:?}", inner_block.name, inner_block.dir, inner_block.flex}
                             scoped_block =  scoped_block.add(GenBlockTup(Rc::new(RefCell::new(inner_block))));
                         } else {
-                            log.error(&format!("Target {} is already exists at  {}:{}: ", name, scoped_block.0.borrow().script_path(), all_chars.line));
+                            recovered_error(log, all_chars.error(format!("Target {} is already exists", name)));
+                            // the duplicate body is read in a block not added in the tree
+                            let mut inner_block = GenBlock::new(BlockType::Target);
+                            inner_block.parent = Some(scoped_block.clone());
+                            scoped_block = GenBlockTup(Rc::new(RefCell::new(inner_block)));
                         }
                     },
                     "eq" => {
@@ -2272,6 +2895,11 @@ pub fn process(log: &Log, file: & PathBuf, block: GenBlockTup) -> Result<(), Box
                         inner_block.script_line = all_chars.line;
                         scoped_block =  scoped_block.add(GenBlockTup(Rc::new(RefCell::new(inner_block))));
                     },
+                    "elif" if parent_type == BlockType::If => {
+                        let mut inner_block = GenBlock::new(BlockType::Elif);
+                        inner_block.script_line = all_chars.line;
+                        scoped_block =  scoped_block.add(GenBlockTup(Rc::new(RefCell::new(inner_block))));
+                    },
                     "neq" => {
                         let mut inner_block = GenBlock::new(BlockType::Neq);
                         inner_block.script_line = all_chars.line;
@@ -2305,6 +2933,36 @@ pub fn process(log: &Log, file: & PathBuf, block: GenBlockTup) -> Result<(), Box
                         inner_block.script_line = all_chars.line;
                         scoped_block =  scoped_block.add(GenBlockTup(Rc::new(RefCell::new(inner_block))));
                     },
+                    "try" => {
+                        let mut inner_block = GenBlock::new(BlockType::Try);
+                        inner_block.script_line = all_chars.line;
+                        scoped_block =  scoped_block.add(GenBlockTup(Rc::new(RefCell::new(inner_block))));
+                    },
+                    "catch" | "finally" => {
+                        // the block belongs to the preceding 'try', 'catch' can't follow 'finally'
+                        let try_block = scoped_block.borrow().children.last().cloned().filter(|last| {
+                            let last = last.borrow();
+                            last.block_type == BlockType::Try && last.children.last().is_none_or(|handler| {
+                                let handler_type = &handler.borrow().block_type;
+                                *handler_type != BlockType::Finally && (type_hdr == "finally" || *handler_type != BlockType::Catch)
+                            })
+                        });
+                        let mut inner_block = GenBlock::new(if type_hdr == "catch" {BlockType::Catch} else {BlockType::Finally});
+                        inner_block.name = if name.is_empty() {None} else {Some(name)};
+                        inner_block.script_line = all_chars.line;
+                        // variables of the 'try' body aren't visible in the handler
+                        inner_block.parent = Some(scoped_block.clone());
+                        let inner_block = GenBlockTup(Rc::new(RefCell::new(inner_block)));
+                        match try_block {
+                            Some(try_block) => try_block.borrow_mut().children.push(inner_block.clone()),
+                            // the body is read in a block not added in the tree
+                            None => recovered_error(log, all_chars.error(format!("A '{}' block doesn't follow a 'try' block", type_hdr)))
+                        }
+                        scoped_block = inner_block
+                    },
+                    "" if body_owner.is_some() => {
+                        scoped_block = body_owner.take().unwrap()
+                    },
                     "" => {
                         let mut inner_block = GenBlock::new(BlockType::Scope);
                         inner_block.script_line = all_chars.line;
@@ -2334,7 +2992,7 @@ pub fn process(log: &Log, file: & PathBuf, block: GenBlockTup) -> Result<(), Box
                         inner_block.script_line = all_chars.line;
                         scoped_block =  scoped_block.add(GenBlockTup(Rc::new(RefCell::new(inner_block))))
                     },
-                    _ => log.error(&format!("unknown block {} of {:?} at  {}:{}:{}", type_hdr, parent_type, scoped_block.borrow().script_path(), all_chars.line, all_chars.line_offset))
+                    _ => recovered_error(log, all_chars.error(format!("unknown block {} of {:?}", type_hdr, parent_type)))
                 }
                 
             },
@@ -2347,14 +3005,23 @@ pub fn process(log: &Log, file: & PathBuf, block: GenBlockTup) -> Result<(), Box
                 drop(rl_block);
                 let parent = scoped_block.parent();
                 match parent {
-                    None => log.error(&format!("Unmatched block {:?} closing found at  {}:{}:{}", scoped_block.borrow().block_type, scoped_block.borrow().script_path(), all_chars.line, all_chars.line_offset)),
+                    None => recovered_error(log, all_chars.error(format!("Unmatched block {:?} closing found", scoped_block.borrow().block_type))),
                     Some(parent) => scoped_block = parent.clone()
                 }
             },
             Lexem::Comment(value) => {
                 log.debug(&format!("Commentary: {}, line: {}/{}", value, all_chars.line, all_chars.line_offset));
+                pending_body = body_owner.take()
             },
-            _ => todo!("unprocessed lexem {:?}", lex)
+            _ => return Err(Box::new(all_chars.error(format!("Unexpected {:?}", lex))))
+        }
+        if let Some(owner) = body_owner {
+            let owner = owner.borrow();
+            let what = match owner.block_type {
+                BlockType::Fun => format!("function {}", owner.name.as_deref().unwrap_or_default()),
+                _ => "'for'".to_string(),
+            };
+            recovered_error(log, owner.script_error(format!("A body of {} isn't specified", what)))
         }
         state = state2;
     }
@@ -1,33 +1,60 @@
 use simcolor::{Colorized};
+use std::{io::Write, sync::Mutex};
+use crate::util::strip_colors;
+
 pub struct Log {
     pub verbose: bool,
     pub debug: bool,
     pub quiet: bool,
-   // writer: Write
+    pub explain: bool,
+    pub sinks: Vec<Mutex<Box<dyn Write + Send>>>, // all output is also teed in, with no colors
 }
 
 impl Log {
     pub fn log(&self, msg: &str) {
-        if self.verbose && !self.quiet {
-            println!("{}", msg); // write!(&mut writer, "{}", msg);
+        if self.verbose {
+            if !self.quiet {
+                println!("{}", msg);
+            }
+            self.tee(msg)
         }
     }
-    
+
     pub fn debug(&self, msg: &str) {
-        if self.debug && !self.quiet {
-            println!("{}", msg);
+        if self.debug {
+            if !self.quiet {
+                println!("{}", msg);
+            }
+            self.tee(msg)
         }
     }
 
     pub fn error(&self, msg: &str) {
+        let msg = format!("{} {}", "Error:".bold(), msg.red());
         if !self.quiet {
-             eprintln!("{} {}", "Error:".bold(), msg.red());
+             eprintln!("{msg}");
         }
-    }   
-    
+        self.tee(&msg)
+    }
+
     pub fn warning(&self, msg: &str) {
-        if self.verbose && !self.quiet {
-             println!("{} {}", "Warning:".italic(), msg.yellow());
+        if self.verbose {
+            let msg = format!("{} {}", "Warning:".italic(), msg.yellow());
+            if !self.quiet {
+                println!("{msg}");
+            }
+            self.tee(&msg)
+        }
+    }
+
+    /// tells why a target was executed or not
+    pub fn explain(&self, msg: &str) {
+        if self.explain {
+            let msg = format!("{} {}", "Explain:".bold(), msg);
+            if !self.quiet {
+                println!("{msg}");
+            }
+            self.tee(&msg)
         }
     }
 
@@ -35,5 +62,28 @@ impl Log {
         if !self.quiet {
             println!("{}", msg);
         }
+        self.tee(msg)
+    }
+
+    pub fn add_sink(&mut self, sink: impl Write + Send + 'static) {
+        self.sinks.push(Mutex::new(Box::new(sink)))
+    }
+
+    pub fn is_teed(&self) -> bool {
+        !self.sinks.is_empty()
+    }
+
+    /// writes a line in all sinks only, the console output is up to a caller
+    pub fn tee(&self, msg: &str) {
+        if self.sinks.is_empty() {
+            return
+        }
+        let line = strip_colors(msg);
+        for sink in &self.sinks {
+            if let Ok(mut sink) = sink.lock() {
+                let _ = writeln!(sink, "{line}");
+                let _ = sink.flush();
+            }
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,451 @@
+// a language server of scripts, it talks the LSP by JSON-RPC messages over stdin and stdout
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    check,
+    fun::{BlockType, GenBlock, GenBlockTup},
+    json::Json,
+    lex::{self, Lexem, LineLexem, ScriptError, Value, VarType, VarVal},
+    log::Log,
+    util,
+};
+
+/// a script opened in an editor
+struct Document {
+    path: PathBuf,
+    text: String,
+    tree: GenBlockTup,
+    targets: Vec<Symbol>,
+    vars: Vec<(String, u32)>, // variable definitions with their lines
+}
+
+/// a target with its lines, counted from 0 as in the LSP
+struct Symbol {
+    name: String,
+    description: String,
+    start: u32,
+    end: u32,
+}
+
+/// serves an editor until it sends 'exit', the variables are predefined in every script
+pub fn serve(vars: &HashMap<String, VarVal>) -> io::Result<()> {
+    let mut input = io::stdin().lock();
+    let mut docs: HashMap<String, Document> = HashMap::new();
+    while let Some(message) = read_message(&mut input)? {
+        let Ok(message) = Json::parse(&message) else {
+            send(&Json::object(vec![
+                ("jsonrpc", Json::str("2.0")),
+                ("id", Json::Null),
+                ("error", error(-32700, "Parse error")),
+            ]))?;
+            continue;
+        };
+        let id = message.get("id").cloned();
+        let method = message.get("method").and_then(Json::as_str).unwrap_or_default();
+        let params = message.get("params").cloned().unwrap_or(Json::Null);
+        let uri = params
+            .at(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let result = match method {
+            "initialize" => Some(capabilities()),
+            "shutdown" => Some(Json::Null),
+            "exit" => break,
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let text = if method == "textDocument/didOpen" {
+                    params.at(&["textDocument", "text"]).and_then(Json::as_str)
+                } else {
+                    params
+                        .get("contentChanges")
+                        .and_then(Json::as_array)
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change.get("text"))
+                        .and_then(Json::as_str)
+                };
+                if let Some(text) = text {
+                    let (doc, diagnostics) = analyze(&uri, text, vars);
+                    docs.insert(uri.clone(), doc);
+                    publish(&uri, diagnostics)?
+                }
+                None
+            }
+            "textDocument/didClose" => {
+                docs.remove(&uri);
+                publish(&uri, Vec::new())?;
+                None
+            }
+            "textDocument/definition" => Some(
+                docs.get(&uri)
+                    .and_then(|doc| definition(doc, position(&params)?))
+                    .unwrap_or(Json::Null),
+            ),
+            "textDocument/hover" => Some(
+                docs.get(&uri)
+                    .and_then(|doc| hover(doc, position(&params)?))
+                    .unwrap_or(Json::Null),
+            ),
+            "textDocument/completion" => Some(completion()),
+            "textDocument/documentSymbol" => Some(Json::Array(
+                docs.get(&uri).map(outline).unwrap_or_default(),
+            )),
+            _ => {
+                // notifications of not supported methods are ignored
+                if id.is_some() {
+                    send(&Json::object(vec![
+                        ("jsonrpc", Json::str("2.0")),
+                        ("id", id.unwrap_or(Json::Null)),
+                        ("error", error(-32601, &format!("Method {method} isn't supported"))),
+                    ]))?
+                }
+                continue;
+            }
+        };
+        if let (Some(id), Some(result)) = (id, result) {
+            send(&Json::object(vec![("jsonrpc", Json::str("2.0")), ("id", id), ("result", result)]))?
+        }
+    }
+    Ok(())
+}
+
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut len = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            if len.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            len = value.trim().parse::<usize>().ok()
+        }
+    }
+    let mut body = vec![0; len.unwrap_or_default()];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).to_string()))
+}
+
+fn send(message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    let mut out = io::stdout().lock();
+    write!(out, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    out.flush()
+}
+
+fn error(code: i32, msg: &str) -> Json {
+    Json::object(vec![("code", Json::Number(code as f64)), ("message", Json::str(msg))])
+}
+
+fn publish(uri: &str, diagnostics: Vec<Json>) -> io::Result<()> {
+    send(&Json::object(vec![
+        ("jsonrpc", Json::str("2.0")),
+        ("method", Json::str("textDocument/publishDiagnostics")),
+        (
+            "params",
+            Json::object(vec![("uri", Json::str(uri)), ("diagnostics", Json::Array(diagnostics))]),
+        ),
+    ]))
+}
+
+fn capabilities() -> Json {
+    Json::object(vec![
+        (
+            "capabilities",
+            Json::object(vec![
+                ("textDocumentSync", Json::Number(1.0)), // a full text is sent on changes
+                ("definitionProvider", Json::Bool(true)),
+                ("hoverProvider", Json::Bool(true)),
+                ("completionProvider", Json::object(vec![])),
+                ("documentSymbolProvider", Json::Bool(true)),
+            ]),
+        ),
+        (
+            "serverInfo",
+            Json::object(vec![("name", Json::str("rb")), ("version", Json::str(crate::version().0))]),
+        ),
+    ])
+}
+
+/// parses and checks the script text, all found problems are returned as diagnostics
+fn analyze(uri: &str, text: &str, vars: &HashMap<String, VarVal>) -> (Document, Vec<Json>) {
+    let path = uri_to_path(uri);
+    let quiet = Log { debug: false, verbose: false, quiet: true, explain: false, sinks: Vec::new() };
+    let mut main = GenBlock::new(BlockType::Main);
+    main.vars = vars.clone();
+    main.vars.insert(crate::SCRIPT.to_string(), VarVal::from_path(&path));
+    if let Some(dir) = path.parent() {
+        main.vars.insert(crate::CWD.to_string(), VarVal::from_path(dir));
+    }
+    let tree = GenBlockTup(Rc::new(RefCell::new(main)));
+    let mut diagnostics = Vec::new();
+    let recovered = lex::recovered_errors();
+    let mut errors = Vec::new();
+    match lex::process_text(&quiet, &path, text, tree.clone()) {
+        Ok(()) => errors = check::issues(&tree),
+        Err(err) => match err.downcast::<ScriptError>() {
+            Ok(err) => errors.push(*err),
+            Err(err) => diagnostics.push(diagnostic(1, 0, &err.to_string())),
+        },
+    }
+    // errors the reader recovered from go first
+    let errors = [lex::recovered_errors_since(recovered), errors].concat();
+    let lexems = lex::read_lexems(&quiet, &path, text).unwrap_or_default();
+    for err in &errors {
+        if Path::new(&err.file) == path {
+            diagnostics.push(diagnostic(err.line, err.column.unwrap_or_default() as u32, &err.msg))
+        } else {
+            // an error of an included script is shown at the include
+            diagnostics.push(diagnostic(include_line(&lexems, &err.file), 0, &err.to_string()))
+        }
+    }
+    let mut targets = Vec::new();
+    let mut vars = Vec::new();
+    let mut blocks = Vec::new();
+    for lexem in &lexems {
+        match &lexem.lexem {
+            Lexem::Variable(name) => vars.push((name.to_string(), lexem.line.saturating_sub(1))),
+            Lexem::BlockHdr(header) => {
+                blocks.push((lex::process_lex_header(&quiet, header, &HashMap::new()).ok(), lexem.line))
+            }
+            Lexem::BlockEnd(_) => {
+                if let Some((Some(header), start)) = blocks.pop()
+                    && header.0 == "target"
+                {
+                    targets.push(Symbol {
+                        name: header.1,
+                        description: header.3,
+                        start: start.saturating_sub(1),
+                        end: lexem.line.saturating_sub(1),
+                    })
+                }
+            }
+            _ => (),
+        }
+    }
+    let doc = Document { path, text: text.to_string(), tree, targets, vars };
+    (doc, diagnostics)
+}
+
+/// a line of the include reading the file, or the first include when the file isn't recognized
+fn include_line(lexems: &[LineLexem], file: &str) -> u32 {
+    let name = Path::new(file).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let includes: Vec<(u32, &str)> = lexems
+        .windows(2)
+        .filter_map(|pair| match (&pair[0].lexem, &pair[1].lexem) {
+            (Lexem::Function(fun), Lexem::Parameter(param)) if fun == "include" => Some((pair[1].line, param.as_str())),
+            _ => None,
+        })
+        .collect();
+    includes
+        .iter()
+        .find(|(_, param)| param.ends_with(&name))
+        .or(includes.first())
+        .map_or(1, |(line, _)| *line)
+}
+
+fn diagnostic(line: u32, column: u32, msg: &str) -> Json {
+    let pos = pos_json(line.saturating_sub(1), column.saturating_sub(1));
+    Json::object(vec![
+        ("range", Json::object(vec![("start", pos.clone()), ("end", pos)])),
+        ("severity", Json::Number(1.0)),
+        ("source", Json::str("rb")),
+        ("message", Json::str(msg.trim())),
+    ])
+}
+
+fn position(params: &Json) -> Option<(u32, u32)> {
+    Some((
+        params.at(&["position", "line"])?.as_u32()?,
+        params.at(&["position", "character"])?.as_u32()?,
+    ))
+}
+
+fn pos_json(line: u32, character: u32) -> Json {
+    Json::object(vec![
+        ("line", Json::Number(line as f64)),
+        ("character", Json::Number(character as f64)),
+    ])
+}
+
+fn range_json(start: u32, end: u32, end_character: u32) -> Json {
+    Json::object(vec![("start", pos_json(start, 0)), ("end", pos_json(end, end_character))])
+}
+
+/// a name under the cursor, a variable in ${..}, a function parameter, or a block header part
+fn word_at(text: &str, line: u32, character: u32) -> Option<String> {
+    let chars: Vec<char> = text.lines().nth(line as usize)?.chars().collect();
+    let pos = (character as usize).min(chars.len());
+    let line: String = chars.iter().collect();
+    let before: String = chars[..pos].iter().collect();
+    if let Some(start) = before.rfind("${")
+        && !before[start..].contains('}')
+    {
+        let name = &line[start + 2..];
+        return Some(name[..name.find('}')?].to_string());
+    }
+    let delimiter = |c: &char| "(),=;{}[]#\":".contains(*c);
+    let start = chars[..pos].iter().rposition(delimiter).map_or(0, |start| start + 1);
+    let end = chars[pos..].iter().position(delimiter).map_or(chars.len(), |end| pos + end);
+    let word: String = chars[start..end].iter().collect();
+    let word = word.trim();
+    let word = word.strip_prefix("target ").unwrap_or(word).trim();
+    (!word.is_empty()).then(|| word.to_string())
+}
+
+fn target_block(doc: &Document, name: &str) -> Option<GenBlockTup> {
+    doc.tree.get_target(&name.to_string())
+}
+
+fn definition(doc: &Document, (line, character): (u32, u32)) -> Option<Json> {
+    let word = word_at(&doc.text, line, character)?;
+    let var = doc
+        .vars
+        .iter()
+        .filter(|(name, _)| *name == word)
+        .min_by_key(|(_, var_line)| if *var_line <= line { line - var_line } else { u32::MAX - var_line });
+    let (path, line) = match var {
+        Some((_, var_line)) => (doc.path.clone(), *var_line),
+        None => {
+            let target = target_block(doc, &word)?;
+            let target = target.borrow();
+            let path = target.vars.get(crate::TARGET_SCRIPT).map(|path| PathBuf::from(path.text()))?;
+            (path, target.script_line.saturating_sub(1))
+        }
+    };
+    Some(Json::object(vec![
+        ("uri", Json::str(path_to_uri(&path))),
+        ("range", range_json(line, line, 0)),
+    ]))
+}
+
+fn hover(doc: &Document, (line, character): (u32, u32)) -> Option<Json> {
+    let word = word_at(&doc.text, line, character)?;
+    let quiet = Log { debug: false, verbose: false, quiet: true, explain: false, sinks: Vec::new() };
+    // a variable of the target under the cursor hides a variable of the script
+    let scope = doc
+        .targets
+        .iter()
+        .find(|target| target.start <= line && line <= target.end)
+        .and_then(|target| target_block(doc, &target.name));
+    let var = scope
+        .as_ref()
+        .and_then(|scope| find_var(scope, &word))
+        .or_else(|| {
+            let var = doc.tree.borrow().vars.get(&word).cloned()?;
+            Some((doc.tree.clone(), var))
+        });
+    let value = match var {
+        Some((block, var)) => {
+            let value = match var.resolved() {
+                Value::Array(values) => format!("[{}]", util::vec_to_str(&values)),
+                Value::Str(value) if var.val_type == VarType::Generic => lex::try_template_value(&quiet, &value, &block.borrow(), &None).unwrap_or_else(|msg| msg),
+                value => value.to_string(),
+            };
+            let kind = match var.val_type {
+                VarType::Generic => var.value.type_name().to_string(),
+                val_type => format!("{val_type:?}"),
+            };
+            format!("**{word}**: `{kind}`\n\n```\n{value}\n```")
+        }
+        None => {
+            let target = target_block(doc, &word)?;
+            let target = target.borrow();
+            format!("**target {word}**\n\n{}", target.flex.as_deref().unwrap_or_default())
+        }
+    };
+    Some(Json::object(vec![(
+        "contents",
+        Json::object(vec![("kind", Json::str("markdown")), ("value", Json::str(value))]),
+    )]))
+}
+
+/// a variable defined in the block or in blocks nested in it
+fn find_var(block: &GenBlockTup, name: &str) -> Option<(GenBlockTup, VarVal)> {
+    if let Some(var) = block.borrow().vars.get(name) {
+        return Some((block.clone(), var.clone()));
+    }
+    block.borrow().children.iter().find_map(|child| find_var(child, name))
+}
+
+fn completion() -> Json {
+    let functions = check::FUNCTIONS.iter().map(|(name, ..)| *name).chain(["target"]);
+    Json::Array(
+        functions
+            .map(|name| {
+                Json::object(vec![
+                    ("label", Json::str(name)),
+                    ("kind", Json::Number(3.0)), // function
+                    ("insertText", Json::str(format!("{name}("))),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn outline(doc: &Document) -> Vec<Json> {
+    doc.targets
+        .iter()
+        .map(|target| {
+            let end_character = doc
+                .text
+                .lines()
+                .nth(target.end as usize)
+                .map_or(0, |line| line.chars().count() as u32);
+            Json::object(vec![
+                ("name", Json::str(&target.name)),
+                ("detail", Json::str(&target.description)),
+                ("kind", Json::Number(12.0)), // function
+                ("range", range_json(target.start, target.end, end_character)),
+                ("selectionRange", range_json(target.start, target.start, 0)),
+            ])
+        })
+        .collect()
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut chars = path.bytes();
+    while let Some(byte) = chars.next() {
+        if byte == b'%'
+            && let (Some(hi), Some(lo)) = (chars.next(), chars.next())
+            && let Ok(decoded) = u8::from_str_radix(&format!("{}{}", hi as char, lo as char), 16)
+        {
+            bytes.push(decoded)
+        } else {
+            bytes.push(byte)
+        }
+    }
+    let path = String::from_utf8_lossy(&bytes).to_string();
+    // a Windows path looks as /C:/dir
+    if cfg!(windows) && path.starts_with('/') && path.get(2..3) == Some(":") {
+        return PathBuf::from(&path[1..]);
+    }
+    PathBuf::from(path)
+}
+
+fn path_to_uri(path: &std::path::Path) -> String {
+    let path = path.display().to_string().replace('\\', "/");
+    let mut res = String::from(if path.starts_with('/') { "file://" } else { "file:///" });
+    for c in path.chars() {
+        match c {
+            ' ' | '%' | '#' | '?' => res += &format!("%{:02X}", c as u32),
+            c => res.push(c),
+        }
+    }
+    res
+}
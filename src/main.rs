@@ -8,7 +8,7 @@ use std::{fs::{self,File},env,
     time::{SystemTime},
     sync::RwLock,
     error::Error,
-    collections::HashMap, ops::ControlFlow};
+    collections::HashMap, ops::ControlFlow, process};
 #[cfg(feature = "release")]
 use std::panic;
 use simcolor::{Colorized};
@@ -18,6 +18,12 @@ mod log;
 mod lex;
 mod fun;
 mod util;
+mod graph;
+mod state;
+mod check;
+mod fmt;
+mod json;
+mod lsp;
 
 use log::Log;
 
@@ -34,7 +40,19 @@ enum CmdOption {
      SpecifiedTargetBuild,
      DryRun,
      Quiet,
-     TargetHelp
+     TargetHelp,
+     KeepGoing,
+     LogFile(String),
+     Jobs(usize),
+     Graph(String),
+     Check,
+     Fmt(Option<String>),
+     Lsp,
+     Explain,
+     Watch,
+     WatchList(String), // internal, a file collecting inputs to watch
+     Job, // internal, the process runs a single target as a job of a parallel build
+     TargetDone(String) // internal, name:state of a target completed by another job
 }
 
 include!("ver.rs");
@@ -48,6 +66,7 @@ static SCRIPT_EXT_PURE: &str = SCRIPT_EXT.split_at(1).1;
 
 pub const CWD : &str = "~cwd~";
 pub const SCRIPT: &str ="~script~";
+pub const TARGET_SCRIPT: &str ="~target-script~"; // a script file where a target is defined, it can be included
 
 pub fn set_property(name: &String, value: &String) {
      if SYSTEM_PROPERTIES.read().unwrap().is_none() {
@@ -142,6 +161,61 @@ fn parse_command<'a>(log: &'a Log, args: &'a [String]) -> (Vec<CmdOption>, Vec<&
                options.push(CmdOption::SpecifiedTargetBuild)
           } else if arg.starts_with("-t") || arg.starts_with("--targethelp") {
                options.push(CmdOption::TargetHelp)
+          } else if arg.starts_with("-k") || arg.starts_with("--keep-going") {
+               options.push(CmdOption::KeepGoing)
+          } else if arg == "-l" || arg.starts_with("--logfile") {
+               arg_n += 1;
+               if arg_n < len {
+                    options.push(CmdOption::LogFile(args[arg_n].to_string()))
+               } else {
+                    log.error("No file path specified after --logfile option")
+               }
+          } else if arg == "--watch" {
+               options.push(CmdOption::Watch)
+          } else if arg == "--watch-list" {
+               arg_n += 1;
+               if arg_n < len {
+                    options.push(CmdOption::WatchList(args[arg_n].to_string()))
+               } else {
+                    log.error("No file path specified after --watch-list option")
+               }
+          } else if arg == "--fmt" {
+               if arg_n + 1 < len && !args[arg_n + 1].starts_with('-') {
+                    arg_n += 1;
+                    options.push(CmdOption::Fmt(Some(args[arg_n].to_string())))
+               } else {
+                    options.push(CmdOption::Fmt(None))
+               }
+          } else if arg == "--check" {
+               options.push(CmdOption::Check)
+          } else if arg == "--lsp" {
+               options.push(CmdOption::Lsp)
+          } else if arg.starts_with("--explain") {
+               options.push(CmdOption::Explain)
+          } else if arg.starts_with("--graph") {
+               arg_n += 1;
+               if arg_n < len {
+                    options.push(CmdOption::Graph(args[arg_n].to_string()))
+               } else {
+                    log.error("No file path specified after --graph option")
+               }
+          } else if arg == "-j" || arg == "--jobs" {
+               let jobs = if arg_n + 1 < len && let Ok(jobs) = args[arg_n + 1].parse::<usize>() {
+                    arg_n += 1;
+                    jobs
+               } else {
+                    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+               };
+               options.push(CmdOption::Jobs(jobs))
+          } else if arg == "--job" {
+               options.push(CmdOption::Job)
+          } else if arg == "--target-done" {
+               arg_n += 1;
+               if arg_n < len {
+                    options.push(CmdOption::TargetDone(args[arg_n].to_string()))
+               } else {
+                    log.error("No target specified after --target-done option")
+               }
           } else if arg == "--" { 
                arg_n += 1;
                if arg_n < len {
@@ -223,7 +297,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             eprintln!("Abnormal RustBee termination")
         }
      }));
-     let mut log = Log {debug : false, verbose : false, quiet : false};
+     let mut log = Log {debug : false, verbose : false, quiet : false, explain: false, sinks: Vec::new()};
      *SYSTEM_PROPERTIES.write().unwrap() = Some(HashMap::new());
      let mut path: Option<String> = None;
      let args: Vec<String> = env::args().collect();
@@ -234,6 +308,26 @@ fn main() -> Result<(), Box<dyn Error>> {
      for target in targets {
           real_targets.push(target.to_string())
      }
+     // options passed to parallel build jobs
+     let mut job_args: Vec<String> = Vec::new();
+     for opt in &options {
+          match opt {
+               CmdOption::Verbose => job_args.push("-v".to_string()),
+               CmdOption::Diagnostics => job_args.push("-d".to_string()),
+               CmdOption::Quiet => job_args.push("-q".to_string()),
+               CmdOption::ForceRebuild => job_args.push("-r".to_string()),
+               CmdOption::DryRun => job_args.push("-y".to_string()),
+               CmdOption::Explain => job_args.push("--explain".to_string()),
+               CmdOption::WatchList(file) => job_args.extend(["--watch-list".to_string(), file.to_string()]),
+               CmdOption::PropertyFile(file) => job_args.extend(["-prop".to_string(), file.to_string()]),
+               _ => ()
+          }
+     }
+     job_args.extend(args.iter().filter(|arg| arg.starts_with("-D")).cloned());
+     if !run_args.is_empty() {
+          job_args.push("--".to_string());
+          job_args.extend(run_args.iter().cloned())
+     }
      let _ = lex_tree.add_var(String::from("~args~"), lex::VarVal::from_vec(run_args));
      let _ = lex_tree.add_var(String::from("~os~"),  lex::VarVal::from_string(std::env::consts::OS));
      let _ = lex_tree.add_var(String::from("~separator~"),  lex::VarVal::from_string(std::path::MAIN_SEPARATOR_STR));
@@ -245,10 +339,18 @@ fn main() -> Result<(), Box<dyn Error>> {
      lex_tree.add_var(String::from(CWD),  lex::VarVal::from_string(&cwd));
      //println!("additional ars {:?}", lex_tree.search_up(&String::from("~args~")));
      let mut target_help = false;
+     let mut jobs = 1;
+     let mut graph_file = None;
      if options.contains(&CmdOption::Quiet) {
           log.quiet = true
      }
-     if !log.quiet {
+     for opt in &options {
+          if let CmdOption::LogFile(file) = opt {
+               log.add_sink(File::create(file)?)
+          }
+     }
+     if !log.quiet && !options.contains(&CmdOption::Job) && !options.contains(&CmdOption::Watch)
+          && !options.contains(&CmdOption::Lsp) {
         // TODO get year from time::
           log.message(&format!("RustBee ({}) v {} © {} D. Rogatkin", "rb".bright().cyan(), version().0, util::year_now()));
           if options.contains(&CmdOption::Version) {
@@ -266,6 +368,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                CmdOption::Verbose => log.verbose = true,
                CmdOption::Diagnostics => log.debug = true,
                CmdOption::Quiet => log.quiet = true,
+               CmdOption::Explain => log.explain = true,
                CmdOption::ScriptFile(file) => {
                     log.log(&format!("Script: {}", file));
                     
@@ -286,11 +389,11 @@ fn main() -> Result<(), Box<dyn Error>> {
                     }
                },
                CmdOption::ForceRebuild => {
-                    let fb = lex::VarVal{val_type:lex::VarType::Bool, value: String::from("true"), values: Vec::new()};
+                    let fb = lex::VarVal::from_bool(true);
                     let _ = &lex_tree.add_var(String::from("~force-build-target~"), fb);
                },
                CmdOption::DryRun => {
-                    let dr = lex::VarVal{val_type:lex::VarType::Bool, value: String::from("true"), values: Vec::new()};
+                    let dr = lex::VarVal::from_bool(true);
                     let _ = &lex_tree.add_var(String::from("~dry-run~"), dr);
                }
                CmdOption::SpecifiedTargetBuild => {
@@ -309,9 +412,34 @@ fn main() -> Result<(), Box<dyn Error>> {
                         }    
                     }
                }
-               CmdOption::TargetHelp => target_help = true
+               CmdOption::TargetHelp => target_help = true,
+               CmdOption::LogFile(file) => log.log(&format!("Log: {}", file)),
+               CmdOption::KeepGoing => {
+                    let _ = &lex_tree.add_var(String::from("~keep-going~"), lex::VarVal::from_bool(true));
+               }
+               CmdOption::Jobs(n) => jobs = *n,
+               CmdOption::Graph(file) => graph_file = Some(file),
+               CmdOption::Check => (),
+               CmdOption::Fmt(file) => if file.is_some() { path = file.clone() },
+               CmdOption::Lsp => (),
+               CmdOption::Watch => (),
+               CmdOption::WatchList(file) => fun::set_watch_list(file),
+               CmdOption::Job => (),
+               CmdOption::TargetDone(done) => {
+                    if let Some((name,state)) = done.rsplit_once(':') {
+                         fun::set_target_result(name, Ok(state == "executed"))
+                    } else {
+                         log.error(&format!("Invalid completed target: {}", done))
+                    }
+               }
           }
      }
+     if options.contains(&CmdOption::Watch) {
+          return fun::watch(&log, &args)
+     }
+     if options.contains(&CmdOption::Lsp) {
+          return Ok(lsp::serve(&lex_tree.borrow().vars.clone())?)
+     }
      
      if path.is_none() {
           let mut paths = fs::read_dir("./").unwrap();
@@ -342,12 +470,36 @@ fn main() -> Result<(), Box<dyn Error>> {
      } else if !path.is_file() {
          return Err(Box::new(format!{"Script file {} isn't a regular file", path.display().to_string().bold()}.default()))
      }
+     if options.iter().any(|opt| matches!(opt, CmdOption::Fmt(_))) {
+          match fmt::format(&log, &path) {
+               Ok(true) => log.message(&format!("The script {} is formatted", path.display().to_string().bold())),
+               Ok(false) => log.log(&format!("The script {} is already formatted", path.display().to_string().bold())),
+               Err(err) => {
+                    log.error(&err.to_string());
+                    io::stdout().flush()?;
+                    process::exit(1)
+               }
+          }
+          return Ok(())
+     }
      let _ = &lex_tree.add_var(String::from(SCRIPT), lex::VarVal::from_path(&path));
      
      let sys_time = SystemTime::now();
      
-     let lex_res = lex::process(&log, &path, lex_tree.clone());
-      if target_help {
+     if let Err(err) = lex::process(&log, &path, lex_tree.clone()) {
+          log.error(&err.to_string());
+          io::stdout().flush()?;
+          process::exit(1)
+     }
+      if options.contains(&CmdOption::Check) {
+          let issues = check::check(&log, &lex_tree);
+          if issues > 0 {
+               log.message(&format!("{} problem(s) found in {}", issues, path.display().to_string().bold()));
+               io::stdout().flush()?;
+               process::exit(1)
+          }
+          log.message(&format!("No problems found in {}", path.display().to_string().bold()))
+      } else if target_help {
           let tree = lex_tree.0.borrow();
           log.message("Targets");
          for child_tree in &tree.children {
@@ -357,8 +509,31 @@ fn main() -> Result<(), Box<dyn Error>> {
                          log.message(&format!("{name} - {}", child.flex.clone().unwrap_or("".to_string()).bright().blue()))
                }
          }
-      } else if lex_res.is_ok() {
-         fun::run(&log, lex_tree, &mut real_targets)?
+      } else if let Some(file) = graph_file {
+          let graph = graph::Graph::new(&lex_tree);
+          fs::write(file, if file.ends_with(".json") { graph.to_json() } else { graph.to_dot() })?;
+          log.log(&format!("Target graph is written in {}", file))
+      } else if let Err(err) = if jobs > 1 && !options.contains(&CmdOption::Job) {
+               fun::run_jobs(&log, lex_tree, &mut real_targets, jobs, &job_args)
+          } else {
+               fun::run(&log, lex_tree, &mut real_targets)
+          } {
+         if err.is::<fun::BuildFailure>() {
+              log.error(&err.to_string());
+              io::stdout().flush()?;
+              process::exit(fun::BUILD_FAILURE_EXIT_CODE)
+         }
+         if err.is::<lex::ScriptError>() {
+              log.error(&err.to_string());
+              io::stdout().flush()?;
+              process::exit(1)
+         }
+         return Err(err)
+      } else if options.contains(&CmdOption::Job)
+          && let Some(target) = real_targets.first()
+          && let Some(Ok(false)) = fun::get_target_result(target) {
+         io::stdout().flush()?;
+         process::exit(fun::JOB_UP_TO_DATE_EXIT_CODE)
       }
      
      if let Ok(elapsed) = sys_time.elapsed() {
@@ -0,0 +1,95 @@
+// a persistent build state, it keeps content hashes of files checked by 'changed' dependencies
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read},
+    path::{MAIN_SEPARATOR_STR, Path},
+    process,
+};
+
+pub const STATE_FILE: &str = ".rb-state";
+
+/// file paths with content hashes of them
+pub type FileHashes = Vec<(String, String)>;
+
+/// the state recorded for the key in the state file of the directory
+pub fn load(dir: &str, key: &str) -> Option<FileHashes> {
+    let file = File::open(state_path(dir)).ok()?;
+    let mut res: Option<FileHashes> = None;
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        let mut parts = line.rsplitn(3, '\t');
+        let (Some(hash), Some(path), Some(line_key)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        if line_key == key {
+            res.get_or_insert_with(Vec::new)
+                .push((path.to_string(), hash.to_string()))
+        }
+    }
+    res
+}
+
+/// replaces the state of the key in the state file of the directory
+pub fn save(dir: &str, key: &str, hashes: &FileHashes) -> io::Result<()> {
+    let path = state_path(dir);
+    // jobs of a parallel build update the state file one by one, the lock is released when the file is closed
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(format!("{path}.lock"))?;
+    lock.lock()?;
+    let mut content = String::new();
+    if let Ok(file) = File::open(&path) {
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            if line.rsplitn(3, '\t').nth(2) != Some(key) {
+                content += &line;
+                content.push('\n')
+            }
+        }
+    }
+    for (file, hash) in hashes {
+        content += &format!("{key}\t{file}\t{hash}\n")
+    }
+    let temp = format!("{path}.{}", process::id());
+    fs::write(&temp, content)?;
+    fs::rename(&temp, &path)
+}
+
+pub fn hash_files(files: &[String]) -> FileHashes {
+    files
+        .iter()
+        .map(|file| {
+            let hash = file_hash(file)
+                .map(|hash| format!("{hash:016x}"))
+                .unwrap_or_default();
+            (file.to_string(), hash)
+        })
+        .collect()
+}
+
+/// 64 bits FNV-1a hash of a file content
+pub fn file_hash(path: impl AsRef<Path>) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut buf = [0u8; 8 * 1024];
+    let mut hash: u64 = 0xcbf29ce484222325;
+    loop {
+        let len = file.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        for byte in &buf[..len] {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    Ok(hash)
+}
+
+fn state_path(dir: &str) -> String {
+    if dir.is_empty() {
+        STATE_FILE.to_string()
+    } else {
+        format!("{dir}{MAIN_SEPARATOR_STR}{STATE_FILE}")
+    }
+}
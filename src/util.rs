@@ -261,6 +261,68 @@ pub fn vec_to_str(arr: &[String]) -> String {
     arr.join("\t")
 }
 
+/// removes ANSI escape sequences, as colors, from a string
+pub fn strip_colors(str: &str) -> String {
+    if !str.contains('\x1b') {
+        return str.to_string();
+    }
+    let mut res = String::with_capacity(str.len());
+    let mut chars = str.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.next() == Some('[') {
+                // a control sequence ends by a char in range @ - ~
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            res.push(c)
+        }
+    }
+    res
+}
+
+/// prerequisites of all rules of a Makefile style dependency file, as produced by
+/// rustc --emit=dep-info or gcc -MD
+pub fn parse_dep_info(content: &str) -> Vec<String> {
+    let mut res: Vec<String> = Vec::new();
+    let content = content.replace("\\\r\n", " ").replace("\\\n", " ");
+    for line in content.lines() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        // a colon of a Windows drive isn't a rule separator
+        let bytes = line.as_bytes();
+        let Some(sep) = (0..bytes.len()).find(|&i| {
+            bytes[i] == b':' && (i + 1 == bytes.len() || bytes[i + 1] == b' ' || bytes[i + 1] == b'\t')
+        }) else {
+            continue;
+        };
+        let mut name = String::new();
+        let mut chars = line[sep + 1..].chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&' ') => name.push(chars.next().unwrap()),
+                '$' if chars.peek() == Some(&'$') => name.push(chars.next().unwrap()),
+                ' ' | '\t' => {
+                    if !name.is_empty() && !res.contains(&name) {
+                        res.push(name.clone())
+                    }
+                    name.clear()
+                }
+                c => name.push(c),
+            }
+        }
+        if !name.is_empty() && !res.contains(&name) {
+            res.push(name)
+        }
+    }
+    res
+}
+
 use std::time::UNIX_EPOCH;
 #[inline]
 pub fn year_now() -> u64 {